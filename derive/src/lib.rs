@@ -2,7 +2,7 @@ use convert_case::{Case, Casing};
 use darling::{FromDeriveInput, FromField, FromMeta, FromVariant};
 use proc_macro::TokenStream;
 use proc_macro2::TokenStream as TokStr;
-use quote::{quote, ToTokens};
+use quote::{format_ident, quote, ToTokens};
 use syn::{parse_macro_input, parse_quote};
 
 #[derive(Debug, FromMeta, Copy, Clone, Eq, PartialEq)]
@@ -63,6 +63,8 @@ pub fn mt_derive(attr: TokenStream, item: TokenStream) -> TokenStream {
     let mut out = quote! {
         #[derive(Debug)]
         #[cfg_attr(feature = "random", derive(GenerateRandom))]
+        #[cfg_attr(feature = "arbitrary", derive(Arbitrary))]
+        #[cfg_attr(feature = "proptest", derive(MtStrategy))]
         #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
     };
 
@@ -83,7 +85,7 @@ pub fn mt_derive(attr: TokenStream, item: TokenStream) -> TokenStream {
             if args.enumset {
                 out.extend(quote! {
                     #[derive(EnumSetType)]
-                    #[enumset(serialize_as_map)]
+                    #[enumset(serialize_repr = "map")]
                 });
 
                 if let Some(repr) = args.repr {
@@ -172,6 +174,82 @@ pub fn mt_derive(attr: TokenStream, item: TokenStream) -> TokenStream {
     out.into()
 }
 
+// #[mt(zlib)] or #[mt(zstd(level = 3, window_log = 24))]
+// with since/until the codec is only used for those versions, e.g. zlib(until = 29), zstd(since = 29)
+#[derive(Debug, Default)]
+struct Compress {
+    level: Option<i32>,      // overridden by SerializeCtx::{zlib,zstd}_level
+    window_log: Option<u32>, // zstd only
+    since: Option<u16>,
+    until: Option<u16>,
+    span: Option<proc_macro2::Span>, // of the level, for errors
+}
+
+impl FromMeta for Compress {
+    fn from_word() -> darling::Result<Self> {
+        Ok(Self::default())
+    }
+
+    fn from_list(items: &[syn::NestedMeta]) -> darling::Result<Self> {
+        #[derive(FromMeta)]
+        struct Params {
+            level: Option<i32>,
+            window_log: Option<u32>,
+            since: Option<u16>,
+            until: Option<u16>,
+        }
+
+        let Params {
+            level,
+            window_log,
+            since,
+            until,
+        } = Params::from_list(items)?;
+
+        let span = items.iter().find_map(|item| match item {
+            syn::NestedMeta::Meta(syn::Meta::NameValue(x)) if x.path.is_ident("level") => {
+                Some(syn::spanned::Spanned::span(&x.lit))
+            }
+            _ => None,
+        });
+
+        Ok(Self {
+            level,
+            window_log,
+            since,
+            until,
+            span,
+        })
+    }
+}
+
+// flate2 panics on levels above 9, so they are rejected here
+fn zlib_level(zlib: &Compress) -> Result<Option<u32>, TokStr> {
+    match zlib.level {
+        None => Ok(None),
+        Some(x @ 0..=9) => Ok(Some(x as u32)),
+        Some(_) => {
+            let span = zlib.span.unwrap_or_else(proc_macro2::Span::call_site);
+            Err(syn::Error::new(span, "zlib level must be between 0 and 9").to_compile_error())
+        }
+    }
+}
+
+// condition for something limited to since..until, None if it is unconditional
+fn version_cond(since: Option<u16>, until: Option<u16>, version: &TokStr) -> Option<TokStr> {
+    let opt = |x: Option<u16>| match x {
+        Some(x) => quote! { Some(#x) },
+        None => quote! { None },
+    };
+
+    if since.is_none() && until.is_none() {
+        return None;
+    }
+
+    let (since, until) = (opt(since), opt(until));
+    Some(quote! { mt_ser::mt_version_in(#version, #since, #until) })
+}
+
 #[derive(Debug, Default, FromDeriveInput, FromVariant, FromField)]
 #[darling(attributes(mt))]
 #[darling(default)]
@@ -184,133 +262,630 @@ struct MtArgs {
     len: Option<syn::Type>,  // must implement MtCfg
     default: bool,           // type must implement Default
     string_repr: bool,       // for enums
-    zlib: bool,
-    zstd: bool,
+    zlib: Option<Compress>,
+    zstd: Option<Compress>,
+    max_decompressed: Option<u64>, // defaults to DeserializeCtx::limits.max_decompressed
+    since: Option<u16>,            // for fields, skipped ones deserialize to Default
+    until: Option<u16>,            // for fields, exclusive
+    version: Option<syn::Type>,    // leading version of a struct or variant, must convert into u64
+    min_version: Option<u64>, // supported versions of a struct or variant, or when a field is present
+    max_version: Option<u64>, // inclusive, required with version, serialized by default
     map_ser: Option<syn::Expr>,
     map_des: Option<syn::Expr>,
+    context: Option<syn::Type>, // map_ser and map_des also get a &mut to the user context
     multiplier: Option<syn::Expr>,
+    bits: Option<u8>,             // for fields, see group_fields
     typename: Option<syn::Ident>, // remote derive
     bounds: Option<syn::WhereClause>,
 }
 
-type Fields<'a> = Vec<(TokStr, &'a syn::Field)>;
+type Fields<'a> = Vec<(TokStr, String, &'a syn::Field)>;
+
+enum FieldGroup<'a, 'b> {
+    Single(&'b (TokStr, String, &'a syn::Field)),
+    Bits(Vec<(&'b (TokStr, String, &'a syn::Field), u8)>, syn::Ident), // with the container
+    Invalid(TokStr), // compile_error! spanned to the field
+}
+
+// consecutive #[mt(bits = N)] fields are packed MSB-first into a container that is closed as
+// soon as it holds 8, 16 or 32 bits, which is the same as any larger big endian container
+fn group_fields<'a, 'b>(fields: &'b Fields<'a>) -> Vec<FieldGroup<'a, 'b>> {
+    let mut groups = Vec::new();
+    let mut bits = Vec::new();
+    let mut total = 0u32;
+    // the sizes no longer add up once a field is left out
+    let mut invalid = false;
+
+    for field in fields.iter() {
+        let args = MtArgs::from_field(field.2).unwrap();
+
+        let Some(n) = args.bits else {
+            if total != 0 && !invalid {
+                panic!("bit fields add up to {total} bits, not 8, 16 or 32");
+            }
+
+            groups.push(FieldGroup::Single(field));
+            continue;
+        };
+
+        if let Err(e) = check_bits(field.2, n) {
+            groups.push(FieldGroup::Invalid(e));
+            invalid = true;
+            continue;
+        }
+
+        check_bits_args(&args);
+        bits.push((field, n));
+        total += n as u32;
+
+        match total {
+            8 | 16 | 32 => {}
+            x if x > 32 && !invalid => panic!("bit fields add up to {x} bits, more than 32"),
+            _ => continue,
+        }
+
+        groups.push(FieldGroup::Bits(
+            std::mem::take(&mut bits),
+            format_ident!("u{total}"),
+        ));
+        total = 0;
+    }
+
+    if total != 0 && !invalid {
+        panic!("bit fields add up to {total} bits, not 8, 16 or 32");
+    }
 
-fn get_fields(fields: &syn::Fields, ident: impl Fn(TokStr) -> TokStr) -> Fields {
+    groups
+}
+
+// a shift by 32 or more would overflow, reported at the attribute of the field
+fn check_bits(field: &syn::Field, n: u8) -> Result<(), TokStr> {
+    if (1..=32).contains(&n) {
+        return Ok(());
+    }
+
+    let attr = field.attrs.iter().find(|a| a.path.is_ident("mt"));
+    let err = match attr {
+        Some(attr) => syn::Error::new_spanned(attr, "bits must be between 1 and 32"),
+        None => syn::Error::new_spanned(field, "bits must be between 1 and 32"),
+    };
+
+    Err(err.to_compile_error())
+}
+
+// the layout of a container must not depend on anything but its fields
+fn check_bits_args(args: &MtArgs) {
+    if !args.const_before.is_empty()
+        || !args.const_after.is_empty()
+        || args.size.is_some()
+        || args.len.is_some()
+        || args.default
+        || args.zlib.is_some()
+        || args.zstd.is_some()
+        || args.since.is_some()
+        || args.until.is_some()
+        || args.min_version.is_some()
+        || args.max_version.is_some()
+        || args.map_ser.is_some()
+        || args.map_des.is_some()
+        || args.context.is_some()
+        || args.multiplier.is_some()
+    {
+        panic!("bits can not be combined with other field attributes");
+    }
+}
+
+// the names of the fields in a container, for errors and traces
+fn bits_name(bits: &[(&(TokStr, String, &syn::Field), u8)]) -> String {
+    bits.iter()
+        .map(|((_, name, _), _)| name.as_str())
+        .collect::<Vec<_>>()
+        .join("|")
+}
+
+fn get_fields(fields: &syn::Fields, ident: impl Fn(TokStr) -> TokStr) -> Fields<'_> {
     match fields {
         syn::Fields::Named(fs) => fs
             .named
             .iter()
-            .map(|f| (ident(f.ident.as_ref().unwrap().to_token_stream()), f))
+            .map(|f| {
+                let name = f.ident.as_ref().unwrap();
+                (ident(name.to_token_stream()), name.to_string(), f)
+            })
             .collect(),
         syn::Fields::Unnamed(fs) => fs
             .unnamed
             .iter()
             .enumerate()
-            .map(|(i, f)| (ident(syn::Index::from(i).to_token_stream()), f))
+            .map(|(i, f)| {
+                (
+                    ident(syn::Index::from(i).to_token_stream()),
+                    i.to_string(),
+                    f,
+                )
+            })
             .collect(),
         syn::Fields::Unit => Vec::new(),
     }
 }
 
-fn serialize_args(args: &MtArgs, code: &mut TokStr) {
-    macro_rules! impl_compress {
-        ($create:expr) => {
-            *code = quote! {
-                let mut __writer = {
-                    let mut __stream = $create;
-                    let __writer = &mut __stream;
+// condition for a field with since/until or min_version/max_version, None if it is always present
+fn field_cond(args: &MtArgs, version: &TokStr, versioned: bool) -> Option<TokStr> {
+    let lit = proc_macro2::Literal::u64_unsuffixed;
+
+    if !versioned && (args.min_version.is_some() || args.max_version.is_some()) {
+        panic!("min_version and max_version on fields require a version on the struct or variant");
+    }
+
+    let range = match (args.min_version, args.max_version) {
+        (None, None) => None,
+        (min, None) => {
+            let min = lit(min.unwrap_or(0));
+            Some(quote! { (#min..).contains(&__version) })
+        }
+        (min, Some(max)) => {
+            let (min, max) = (lit(min.unwrap_or(0)), lit(max));
+            Some(quote! { (#min..=#max).contains(&__version) })
+        }
+    };
+
+    let conds: Vec<_> = [version_cond(args.since, args.until, version), range]
+        .into_iter()
+        .flatten()
+        .collect();
+
+    if conds.is_empty() {
+        None
+    } else {
+        Some(quote! { #(#conds)&&* })
+    }
+}
+
+// the leading version of a struct or variant, fields see it as __version. it is looked up
+// by path in SerializeCtx::struct_versions, without an entry it is max_version
+fn serialize_version(args: &MtArgs, code: &mut TokStr, typename: &str, mode: SerMode) {
+    let Some(ty) = &args.version else {
+        return;
+    };
+
+    let lit = proc_macro2::Literal::u64_suffixed;
+    let max = args
+        .max_version
+        .map(lit)
+        .expect("version requires max_version");
+    let min = lit(args.min_version.unwrap_or(0));
+    let write = mode.call(quote! { &__version }, quote! { mt_ser::DefCfg });
+
+    *code = quote! {
+        let __version = match __ctx.struct_versions.get(#typename) {
+            Some(&v) if (#min..=#max).contains(&v) => v,
+            Some(&v) => return Err(mt_ser::SerializeError::UnsupportedVersion(#typename, v)),
+            None => #max,
+        };
+        let __version = <#ty as core::convert::TryFrom<u64>>::try_from(__version)?;
+        #write?;
+        #code
+    };
+}
+
+fn deserialize_version(args: &MtArgs, code: &mut TokStr, typename: &str, mode: Mode) {
+    let Some(ty) = &args.version else {
+        return;
+    };
+
+    let lit = proc_macro2::Literal::u64_unsuffixed;
+    let max = args
+        .max_version
+        .map(lit)
+        .expect("version requires max_version");
+    let min = lit(args.min_version.unwrap_or(0));
+    let read = mode.owned().call(quote! { mt_ser::DefCfg });
+
+    *code = quote! {
+        let __version: #ty = #read?;
+        if !(#min..=#max).contains(&__version) {
+            return Err(mt_ser::DeserializeError::UnsupportedVersion(#typename, __version.into()));
+        }
+        #code
+    };
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum SerMode {
+    Sync,
+    Async,
+    Len, // adds up the length in __len instead of writing
+}
+
+impl SerMode {
+    // serialize value, a reference, to __writer
+    fn call(self, value: impl ToTokens, cfg: impl ToTokens) -> TokStr {
+        match self {
+            SerMode::Len => {
+                quote! {
+                    mt_ser::mt_add_len(
+                        &mut __len,
+                        mt_ser::MtSerialize::mt_serialized_len_with::<#cfg>(#value, __ctx),
+                    )
+                }
+            }
+            SerMode::Sync => {
+                quote! { mt_ser::MtSerialize::mt_serialize_with::<#cfg>(#value, __writer, __ctx) }
+            }
+            SerMode::Async => {
+                quote! {
+                    mt_ser::MtAsyncSerialize::mt_serialize_async_with::<#cfg, _>(#value, __writer, __ctx).await
+                }
+            }
+        }
+    }
+}
+
+fn serialize_args(args: &MtArgs, code: &mut TokStr, mode: SerMode) {
+    let version = quote! { __ctx.version };
+
+    // the compressed length is only known after running the encoder, the parts inside
+    // are still added up so that the errors are the same as when serializing
+    if mode == SerMode::Len {
+        for codec in [&args.zlib, &args.zstd].into_iter().flatten() {
+            *code = match version_cond(codec.since, codec.until, &version) {
+                Some(cond) => quote! {
                     #code
-                    __stream.finish()?
-                };
+                    if #cond {
+                        __len = None;
+                    }
+                },
+                None => quote! {
+                    #code
+                    __len = None;
+                },
             };
+        }
+    }
+
+    // for other versions the code writes to __writer directly
+    macro_rules! impl_compress {
+        ($codec:expr, $($create:tt)*) => {
+            if mode == SerMode::Async {
+                // the encoder borrows the writer, finishing it leaves the writer open
+                let compressed = quote! {
+                    {
+                        let mut __stream = $($create)*;
+                        {
+                            let __writer = &mut __stream;
+                            #code
+                        }
+                        mt_ser::tokio::io::AsyncWriteExt::shutdown(&mut __stream).await?;
+                    }
+                };
+
+                *code = match version_cond($codec.since, $codec.until, &version) {
+                    Some(cond) => quote! { if #cond #compressed else { #code } },
+                    None => compressed,
+                };
+            } else {
+                let compressed = quote! {
+                    {
+                        let mut __stream = $($create)*;
+                        let __writer = &mut __stream;
+                        #code
+                        __stream.finish()?
+                    }
+                };
+
+                *code = match version_cond($codec.since, $codec.until, &version) {
+                    Some(cond) => quote! {
+                        let mut __writer = if #cond {
+                            #compressed
+                        } else {
+                            #code
+                            __writer
+                        };
+                    },
+                    None => quote! { let mut __writer = #compressed; },
+                };
+            }
         };
     }
 
-    if args.zlib {
-        impl_compress!(mt_ser::flate2::write::ZlibEncoder::new(
-            __writer,
-            mt_ser::flate2::Compression::default()
-        ));
+    if let Some(zlib) = &args.zlib {
+        if zlib.window_log.is_some() {
+            panic!("window_log is only supported for zstd");
+        }
+
+        let level = match zlib_level(zlib) {
+            Ok(x) => x,
+            Err(e) => {
+                *code = e;
+                return;
+            }
+        };
+
+        // the context level is not checked at compile time, so it is clamped
+        match mode {
+            SerMode::Sync => {
+                let level = match level {
+                    Some(x) => quote! { #x },
+                    None => quote! { mt_ser::flate2::Compression::default().level() },
+                };
+
+                impl_compress!(zlib, mt_ser::flate2::write::ZlibEncoder::new(
+                    __writer,
+                    mt_ser::flate2::Compression::new(__ctx.zlib_level.unwrap_or(#level).min(9))
+                ));
+            }
+            SerMode::Async => {
+                let level = match level {
+                    Some(x) => quote! { Some(#x) },
+                    None => quote! { None },
+                };
+
+                impl_compress!(zlib, mt_ser::async_compression::tokio::write::ZlibEncoder::with_quality(
+                    mt_ser::KeepOpen(&mut *__writer),
+                    match __ctx.zlib_level.or(#level) {
+                        Some(x) => mt_ser::async_compression::Level::Precise(x.min(9) as i32),
+                        None => mt_ser::async_compression::Level::Default,
+                    },
+                ));
+            }
+            SerMode::Len => {}
+        }
     }
 
-    if args.zstd {
-        impl_compress!(mt_ser::zstd::stream::write::Encoder::new(__writer, 0)?);
+    if let Some(zstd) = &args.zstd {
+        let level = zstd.level.unwrap_or(0);
+
+        match mode {
+            SerMode::Sync => {
+                let window_log = zstd
+                    .window_log
+                    .map(|x| quote! { __encoder.window_log(#x)?; });
+
+                impl_compress!(zstd, {
+                    let mut __encoder = mt_ser::zstd::stream::write::Encoder::new(
+                        __writer,
+                        __ctx.zstd_level.unwrap_or(#level),
+                    )?;
+                    #window_log
+                    __encoder
+                });
+            }
+            SerMode::Async => {
+                let params = zstd.window_log.map(|x| {
+                    quote! { mt_ser::async_compression::zstd::CParameter::window_log(#x) }
+                });
+
+                impl_compress!(zstd, mt_ser::async_compression::tokio::write::ZstdEncoder::with_quality_and_params(
+                    mt_ser::KeepOpen(&mut *__writer),
+                    mt_ser::async_compression::Level::Precise(__ctx.zstd_level.unwrap_or(#level)),
+                    &[#params],
+                ));
+            }
+            SerMode::Len => {}
+        }
     }
 
     if let Some(size) = &args.size {
-        *code = quote! {
-            mt_ser::MtSerialize::mt_serialize::<#size>(&{
-                let mut __buf = Vec::new();
-                let __writer = &mut __buf;
-                #code
-                __buf
-            }, __writer)?;
+        *code = if mode == SerMode::Len {
+            quote! {
+                {
+                    let __inner = {
+                        let mut __len = Some(0usize);
+                        #code
+                        __len
+                    };
+                    mt_ser::mt_add_sized_len::<#size>(&mut __len, __inner)?;
+                }
+            }
+        } else {
+            let write = mode.call(quote! { &__buf }, size);
+
+            quote! {
+                {
+                    let mut __buf = mt_ser::__private::Vec::new();
+                    {
+                        let __writer = &mut __buf;
+                        #code
+                    }
+                    #write?;
+                }
+            }
         };
     }
 
     for x in args.const_before.iter().rev() {
+        let write = mode.call(quote! { &(#x) }, quote! { mt_ser::DefCfg });
         *code = quote! {
-            #x.mt_serialize::<mt_ser::DefCfg>(__writer)?;
+            #write?;
             #code
         }
     }
 
     for x in args.const_after.iter() {
+        let write = mode.call(quote! { &(#x) }, quote! { mt_ser::DefCfg });
         *code = quote! {
             #code
-            #x.mt_serialize::<mt_ser::DefCfg>(__writer)?;
+            #write?;
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Mode {
+    Owned,
+    Borrowed,
+    Async,
+}
+
+impl Mode {
+    // deserialize a value of inferred type from __reader
+    fn call(self, cfg: impl ToTokens) -> TokStr {
+        match self {
+            Mode::Owned => {
+                quote! { mt_ser::MtDeserialize::mt_deserialize_with::<#cfg>(__reader, __ctx) }
+            }
+            Mode::Borrowed => {
+                quote! {
+                    mt_ser::MtDeserializeBorrowed::mt_deserialize_borrowed_with::<#cfg>(__reader, __ctx)
+                }
+            }
+            Mode::Async => {
+                quote! { mt_ser::mt_deserialize_async_with::<_, #cfg, _>(__reader, __ctx).await }
+            }
+        }
+    }
+
+    // consts, discriminants and sizes do not borrow
+    fn owned(self) -> Self {
+        match self {
+            Mode::Borrowed => Mode::Owned,
+            x => x,
         }
     }
 }
 
-fn deserialize_args(args: &MtArgs, code: &mut TokStr) {
+fn deserialize_args(args: &MtArgs, code: &mut TokStr, mode: Mode) {
+    let max_decompressed = match args.max_decompressed {
+        Some(x) => quote! { #x },
+        None => quote! { __ctx.limits.max_decompressed },
+    };
+    let version = quote! { __ctx.version };
+
+    // for other versions the code reads from __reader directly
+    // errors are reported at their offset into the decompressed data
     macro_rules! impl_compress {
-        ($create:expr) => {
-            *code = quote! {
+        ($codec:expr, $($create:tt)*) => {
+            let name = stringify!($codec);
+            let result = if mode == Mode::Async {
+                quote! {
+                    async {
+                        let __reader = &mut __owned_reader;
+                        Ok::<_, mt_ser::DeserializeError>(#code)
+                    }.await
+                }
+            } else {
+                quote! {
+                    (|| -> Result<_, mt_ser::DeserializeError> {
+                        let __reader = &mut __owned_reader;
+                        Ok(#code)
+                    })()
+                }
+            };
+
+            let compressed = quote! {
                 {
-                    let mut __owned_reader = $create;
-                    let __reader = &mut __owned_reader;
+                    let mut __owned_reader = mt_ser::LimitRead::new($($create)*, #max_decompressed);
+                    let __result = #result;
 
-                    #code
+                    __result.map_err(|e| e.in_section(#name, __owned_reader.pos()))?
                 }
-            }
+            };
+
+            *code = match version_cond($codec.since, $codec.until, &version) {
+                Some(cond) => quote! { if #cond { #compressed } else { #code } },
+                None => compressed,
+            };
         };
     }
 
-    if args.zlib {
-        impl_compress!(mt_ser::flate2::read::ZlibDecoder::new(mt_ser::WrapRead(
-            __reader
-        )));
+    if let Some(zlib) = &args.zlib {
+        if mode == Mode::Async {
+            impl_compress!(
+                zlib,
+                mt_ser::async_compression::tokio::bufread::ZlibDecoder::new(
+                    mt_ser::tokio::io::BufReader::new(&mut *__reader)
+                )
+            );
+        } else {
+            impl_compress!(
+                zlib,
+                mt_ser::flate2::read::ZlibDecoder::new(mt_ser::WrapRead(__reader))
+            );
+        }
     }
 
-    if args.zstd {
-        impl_compress!(mt_ser::zstd::stream::read::Decoder::new(mt_ser::WrapRead(
-            __reader
-        ))?);
+    // the decoder has to allow windows beyond its default maximum
+    if let Some(zstd) = &args.zstd {
+        match (mode, zstd.window_log) {
+            (Mode::Async, None) => {
+                impl_compress!(
+                    zstd,
+                    mt_ser::async_compression::tokio::bufread::ZstdDecoder::new(
+                        mt_ser::tokio::io::BufReader::new(&mut *__reader)
+                    )
+                );
+            }
+            (Mode::Async, Some(x)) => {
+                impl_compress!(
+                    zstd,
+                    mt_ser::async_compression::tokio::bufread::ZstdDecoder::with_params(
+                        mt_ser::tokio::io::BufReader::new(&mut *__reader),
+                        &[mt_ser::async_compression::zstd::DParameter::window_log_max(#x)],
+                    )
+                );
+            }
+            (_, window_log) => {
+                let window_log = window_log.map(|x| quote! { __decoder.window_log_max(#x)?; });
+
+                impl_compress!(zstd, {
+                    let mut __decoder =
+                        mt_ser::zstd::stream::read::Decoder::new(mt_ser::WrapRead(__reader))?;
+                    #window_log
+                    __decoder
+                });
+            }
+        }
     }
 
     if let Some(size) = &args.size {
-        *code = quote! {
-            {
-                let __size = #size::mt_deserialize::<DefCfg>(__reader)? as u64;
-                let mut __owned_reader = std::io::Read::take(
-                    mt_ser::WrapRead(__reader),
-                    __size,
-                );
-                let __reader = &mut __owned_reader;
+        let read_size = mode.owned().call(quote! { mt_ser::DefCfg });
 
-                let __result = { #code };
+        *code = match mode {
+            Mode::Owned => quote! {
+                {
+                    let __size: #size = #read_size?;
+                    let mut __owned_reader = mt_ser::io::Read::take(
+                        mt_ser::WrapRead(__reader),
+                        __size as u64,
+                    );
+                    let __reader = &mut __owned_reader;
 
-                std::io::Read::read_to_end(
-                    __reader,
-                    &mut Vec::with_capacity(__reader.limit() as usize),
-                )?;
+                    let __result = { #code };
 
-                __result
-            }
+                    mt_ser::mt_drain(__reader)?;
+
+                    __result
+                }
+            },
+            Mode::Borrowed => quote! {
+                {
+                    let __size: #size = #read_size?;
+                    let mut __owned_reader = mt_ser::mt_take_borrowed(__reader, __size as usize);
+                    let __reader = &mut __owned_reader;
+
+                    #code
+                }
+            },
+            Mode::Async => quote! {
+                {
+                    let __size: #size = #read_size?;
+                    let mut __owned_reader = mt_ser::tokio::io::AsyncReadExt::take(
+                        &mut *__reader,
+                        __size as u64,
+                    );
+                    let __reader = &mut __owned_reader;
+
+                    let __result = { #code };
+
+                    mt_ser::tokio::io::copy(__reader, &mut mt_ser::tokio::io::sink()).await?;
+
+                    __result
+                }
+            },
         };
     }
 
+    let read_const = mode.owned().call(quote! { mt_ser::DefCfg });
     let impl_const = |want: &syn::Expr| {
         quote! {
             {
@@ -319,11 +894,11 @@ fn deserialize_args(args: &MtArgs, code: &mut TokStr) {
                 }
 
                 let want = #want;
-                let got = mt_ser::MtDeserialize::mt_deserialize::<DefCfg>(__reader)?;
+                let got = #read_const?;
 
                 if !eq_same_type(&want, &got) {
                     return Err(mt_ser::DeserializeError::InvalidConst(
-                        Box::new(want), Box::new(got)
+                        mt_ser::__private::Box::new(want), mt_ser::__private::Box::new(got)
                     ));
                 }
             }
@@ -352,103 +927,325 @@ fn deserialize_args(args: &MtArgs, code: &mut TokStr) {
     }
 }
 
-fn serialize_fields(fields: &Fields) -> TokStr {
-    fields
+fn serialize_fields(fields: &Fields, path: &str, mode: SerMode, versioned: bool) -> TokStr {
+    group_fields(fields)
+        .into_iter()
+        .map(|group| match group {
+            FieldGroup::Single((ident, name, field)) => {
+                serialize_field(ident, name, field, path, mode, versioned)
+            }
+            FieldGroup::Bits(bits, container) => serialize_bits(&bits, &container, path, mode),
+            FieldGroup::Invalid(e) => e,
+        })
+        .collect()
+}
+
+fn serialize_bits(
+    bits: &[(&(TokStr, String, &syn::Field), u8)],
+    container: &syn::Ident,
+    path: &str,
+    mode: SerMode,
+) -> TokStr {
+    let names = bits_name(bits);
+    let write = mode.call(
+        quote! { &(__bits as #container) },
+        quote! { mt_ser::DefCfg },
+    );
+    let fields: TokStr = bits
         .iter()
-        .map(|(ident, field)| {
-            let args = MtArgs::from_field(field).unwrap();
-            let def = parse_quote! { mt_ser::DefCfg };
-            let len = args.len.as_ref().unwrap_or(&def);
+        .map(|((ident, name, _), n)| {
+            quote! {
+                let __x = mt_ser::MtBits::to_bits(#ident);
+                if u64::from(__x) >> #n != 0 {
+                    return Err(mt_ser::SerializeError::BitsOverflow(__x, #n).in_field(#path, #name));
+                }
+                __bits = __bits << #n | u64::from(__x);
+            }
+        })
+        .collect();
 
-            let mut code = quote! { #ident };
+    quote! {
+        {
+            let mut __bits = 0u64;
+            #fields
+            #write.map_err(|e| e.in_field(#path, #names))?;
+        }
+    }
+}
 
-            if let Some(multiplier) = &args.multiplier {
-                code = quote! {
-                    &((#code) * (#multiplier))
-                };
-            }
+fn serialize_field(
+    ident: &TokStr,
+    name: &str,
+    field: &syn::Field,
+    path: &str,
+    mode: SerMode,
+    versioned: bool,
+) -> TokStr {
+    let args = MtArgs::from_field(field).unwrap();
+    let def = parse_quote! { mt_ser::DefCfg };
+    let len = args.len.as_ref().unwrap_or(&def);
+
+    let mut code = quote! { #ident };
+
+    if let Some(multiplier) = &args.multiplier {
+        code = quote! {
+            &((#code) * (#multiplier))
+        };
+    }
 
-            if let Some(map) = &args.map_ser {
-                code = quote! {
-                    {
-                        fn call_ser_result<I, O>(
-                            f: impl FnOnce(I) -> Result<O, mt_ser::SerializeError>,
-                            i: I
-                        ) -> Result<O, mt_ser::SerializeError> {
-                            f(i)
-                        }
+    check_context_args(&args);
 
-                        &call_ser_result(#map, #code)?
-                    }
-                };
+    if let (Some(map), Some(ty)) = (&args.map_ser, &args.context) {
+        code = quote! {
+            {
+                fn call_ser_result<I, O, X>(
+                    f: impl FnOnce(I, &mut X) -> Result<O, mt_ser::SerializeError>,
+                    i: I,
+                    x: &mut X,
+                ) -> Result<O, mt_ser::SerializeError> {
+                    f(i, x)
+                }
+
+                &call_ser_result(#map, #code, __ctx.user::<#ty>()?)?
             }
+        };
+    } else if let Some(map) = &args.map_ser {
+        code = quote! {
+            {
+                fn call_ser_result<I, O>(
+                    f: impl FnOnce(I) -> Result<O, mt_ser::SerializeError>,
+                    i: I
+                ) -> Result<O, mt_ser::SerializeError> {
+                    f(i)
+                }
 
-            code = quote! { mt_ser::MtSerialize::mt_serialize::<#len>(#code, __writer)?; };
+                &call_ser_result(#map, #code)?
+            }
+        };
+    }
 
-            serialize_args(&args, &mut code);
+    let write = mode.call(code, len);
+    code = quote! { #write?; };
 
-            code
+    serialize_args(&args, &mut code, mode);
+
+    let code = match mode {
+        SerMode::Sync => quote! {
+            // compressors take the writer by value, so hand the closure a reborrow
+            (|__writer: &mut _| -> Result<(), mt_ser::SerializeError> {
+                #code
+                Ok(())
+            })(&mut *__writer)
+            .map_err(|e| e.in_field(#path, #name))?;
+        },
+        SerMode::Async => quote! {
+            async {
+                #code
+                Ok::<(), mt_ser::SerializeError>(())
+            }
+            .await
+            .map_err(|e| e.in_field(#path, #name))?;
+        },
+        SerMode::Len => quote! {
+            (|| -> Result<(), mt_ser::SerializeError> {
+                #code
+                Ok(())
+            })()
+            .map_err(|e| e.in_field(#path, #name))?;
+        },
+    };
+
+    match field_cond(&args, &quote! { __ctx.version }, versioned) {
+        Some(cond) => quote! { if #cond { #code } },
+        None => code,
+    }
+}
+
+fn deserialize_fields(fields: &Fields, path: &str, mode: Mode, versioned: bool) -> TokStr {
+    group_fields(fields)
+        .into_iter()
+        .map(|group| match group {
+            FieldGroup::Single((ident, name, field)) => {
+                deserialize_field(ident, name, field, path, mode, versioned)
+            }
+            FieldGroup::Bits(bits, container) => deserialize_bits(&bits, &container, path, mode),
+            FieldGroup::Invalid(e) => e,
         })
         .collect()
 }
 
-fn deserialize_fields(fields: &Fields) -> TokStr {
-    fields
+// the container is traced as a whole, its fields share its bytes
+fn deserialize_bits(
+    bits: &[(&(TokStr, String, &syn::Field), u8)],
+    container: &syn::Ident,
+    path: &str,
+    mode: Mode,
+) -> TokStr {
+    let names = bits_name(bits);
+    let read = mode.owned().call(quote! { mt_ser::DefCfg });
+
+    let result = quote! { (|| -> Result<#container, mt_ser::DeserializeError> { Ok(#read?) })() };
+    let result = if mode == Mode::Async {
+        quote! { async { Ok::<#container, mt_ser::DeserializeError>(#read?) }.await }
+    } else if mode == Mode::Owned {
+        quote! {
+            {
+                if let Some(__trace) = &mut __ctx.trace {
+                    __trace.enter(#path, #names);
+                }
+
+                let __result = #result;
+
+                if let Some(__trace) = &mut __ctx.trace {
+                    use mt_ser::__private::{TraceDebug as _, TraceNoDebug as _};
+
+                    __trace.leave(__result.as_ref().ok().map(|__value| {
+                        (&mt_ser::__private::TraceValue(__value)).trace_debug()
+                    }));
+                }
+
+                __result
+            }
+        }
+    } else {
+        result
+    };
+
+    let mut shift = bits.iter().map(|(_, n)| *n as u32).sum::<u32>();
+    let fields: TokStr = bits
         .iter()
-        .map(|(ident, field)| {
-            let args = MtArgs::from_field(field).unwrap();
+        .map(|((ident, name, field), n)| {
+            shift -= *n as u32;
+            let mask = (1u64 << n) - 1;
+            let ty = &field.ty;
 
-            let def = parse_quote! { mt_ser::DefCfg };
-            let len = args.len.as_ref().unwrap_or(&def);
-            let mut code = quote! { mt_ser::MtDeserialize::mt_deserialize::<#len>(__reader) };
+            quote! {
+                let #ident: #ty = mt_ser::MtBits::from_bits((__bits >> #shift & #mask) as u32)
+                    .map_err(|e| e.in_field(#path, #name))?;
+            }
+        })
+        .collect();
+
+    quote! {
+        let __bits = u64::from(#result.map_err(|e| e.in_field(#path, #names))?);
+        #fields
+    }
+}
+
+fn deserialize_field(
+    ident: &TokStr,
+    name: &str,
+    field: &syn::Field,
+    path: &str,
+    mode: Mode,
+    versioned: bool,
+) -> TokStr {
+    let args = MtArgs::from_field(field).unwrap();
+
+    let def = parse_quote! { mt_ser::DefCfg };
+    let len = args.len.as_ref().unwrap_or(&def);
+    // compressed sections are decoded into an owned buffer and can not be borrowed from
+    let mut code = if args.zlib.is_some() || args.zstd.is_some() {
+        mode.owned().call(len)
+    } else {
+        mode.call(len)
+    };
+
+    if args.default {
+        code = quote! {
+            mt_ser::OrDefault::or_default(#code)
+        };
+    }
+
+    code = quote! {
+        (#code)?
+    };
+
+    deserialize_args(&args, &mut code, mode);
+
+    if let (Some(map), Some(ty)) = (&args.map_des, &args.context) {
+        code = quote! {
+            {
+                fn call_des_result<I, O, X>(
+                    f: impl FnOnce(I, &mut X) -> Result<O, mt_ser::DeserializeError>,
+                    i: I,
+                    x: &mut X,
+                ) -> Result<O, mt_ser::DeserializeError> {
+                    f(i, x)
+                }
+
+                call_des_result(#map, #code, __ctx.user::<#ty>()?)?
+            }
+        };
+    } else if let Some(map) = &args.map_des {
+        code = quote! {
+            {
+                fn call_des_result<I, O>(
+                    f: impl FnOnce(I) -> Result<O, mt_ser::DeserializeError>,
+                    i: I
+                ) -> Result<O, mt_ser::DeserializeError> {
+                    f(i)
+                }
+
+                call_des_result(#map, #code)?
+            }
+        };
+    }
+
+    if let Some(multiplier) = &args.multiplier {
+        code = quote! {
+            {
+                fn div_same_type<D, T: core::ops::Div<D, Output = T>>(a: T, b: D) -> T {
+                    a / b
+                }
 
-            if args.default {
-                code = quote! {
-                    mt_ser::OrDefault::or_default(#code)
-                };
+                div_same_type(#code, #multiplier)
             }
+        }
+    }
 
-            code = quote! {
-                (#code)?
-            };
+    let ty = &field.ty;
 
-            deserialize_args(&args, &mut code);
+    let result = if mode == Mode::Async {
+        quote! { async { Ok::<#ty, mt_ser::DeserializeError>(#code) }.await }
+    } else {
+        quote! { (|| -> Result<#ty, mt_ser::DeserializeError> { Ok(#code) })() }
+    };
 
-            if let Some(map) = &args.map_des {
-                code = quote! {
-                    {
-                        fn call_des_result<I, O>(
-                            f: impl FnOnce(I) -> Result<O, mt_ser::DeserializeError>,
-                            i: I
-                        ) -> Result<O, mt_ser::DeserializeError> {
-                            f(i)
-                        }
+    // only the owned derive has a DeserializeCtx that can carry a trace
+    let result = if mode == Mode::Owned {
+        quote! {
+            {
+                if let Some(__trace) = &mut __ctx.trace {
+                    __trace.enter(#path, #name);
+                }
 
-                        call_des_result(#map, #code)?
-                    }
-                };
-            }
+                let __result = #result;
 
-            if let Some(multiplier) = &args.multiplier {
-                code = quote! {
-                    {
-                        fn div_same_type<D, T: std::ops::Div<D, Output = T>>(a: T, b: D) -> T {
-                            a / b
-                        }
+                if let Some(__trace) = &mut __ctx.trace {
+                    use mt_ser::__private::{TraceDebug as _, TraceNoDebug as _};
 
-                        div_same_type(#code, #multiplier)
-                    }
+                    __trace.leave(__result.as_ref().ok().map(|__value| {
+                        (&mt_ser::__private::TraceValue(__value)).trace_debug()
+                    }));
                 }
-            }
 
-            quote! {
-                let #ident = #code;
+                __result.map_err(|e| e.in_field(#path, #name))?
             }
-        })
-        .collect()
+        }
+    } else {
+        quote! { #result.map_err(|e| e.in_field(#path, #name))? }
+    };
+
+    match field_cond(&args, &quote! { __ctx.version }, versioned) {
+        Some(cond) => quote! {
+            let #ident = if #cond { #result } else { Default::default() };
+        },
+        None => quote! { let #ident = #result; },
+    }
 }
 
-fn get_fields_struct(input: &syn::Fields) -> (Fields, TokStr) {
+fn get_fields_struct(input: &syn::Fields) -> (Fields<'_>, TokStr) {
     let ident_fn = match input {
         syn::Fields::Unnamed(_) => |f| {
             quote! {
@@ -459,9 +1256,10 @@ fn get_fields_struct(input: &syn::Fields) -> (Fields, TokStr) {
     };
 
     let fields = get_fields(input, ident_fn);
-    let fields_comma: TokStr = fields
-        .iter()
-        .rfold(TokStr::new(), |after, (ident, _)| quote! { #ident, #after });
+    let fields_comma: TokStr = fields.iter().rfold(
+        TokStr::new(),
+        |after, (ident, _, _)| quote! { #ident, #after },
+    );
 
     let fields_struct = match input {
         syn::Fields::Named(_) => quote! { { #fields_comma } },
@@ -487,6 +1285,10 @@ fn get_repr(input: &syn::DeriveInput, args: &MtArgs) -> syn::Type {
 }
 
 fn iter_variants(e: &syn::DataEnum, args: &MtArgs, mut f: impl FnMut(&syn::Variant, &syn::Expr)) {
+    if args.version.is_some() {
+        panic!("version on an enum goes on its variants, after the discriminant");
+    }
+
     let mut discr = parse_quote! { 0 };
 
     for v in e.variants.iter() {
@@ -506,6 +1308,7 @@ fn iter_variants(e: &syn::DataEnum, args: &MtArgs, mut f: impl FnMut(&syn::Varia
 fn make_impl(
     traitname: TokStr,
     input: &syn::DeriveInput,
+    impl_generics: &syn::Generics,
     typename: &syn::Ident,
     args: &MtArgs,
     code: TokStr,
@@ -533,38 +1336,39 @@ fn make_impl(
 
     quote! {
         #[automatically_derived]
-        impl #generics #traitname for #typename #generics #bounds { #code }
+        impl #impl_generics #traitname for #typename #generics #bounds { #code }
     }
     .into()
 }
 
-#[proc_macro_derive(MtSerialize, attributes(mt))]
-pub fn derive_serialize(input: TokenStream) -> TokenStream {
-    let input = parse_macro_input!(input as syn::DeriveInput);
-    let args = MtArgs::from_derive_input(&input).unwrap();
+fn serialize_body(input: &syn::DeriveInput, args: &MtArgs, mode: SerMode) -> TokStr {
     let typename = args.typename.as_ref().unwrap_or(&input.ident);
 
     let mut code = match &input.data {
         syn::Data::Enum(e) => {
-            let repr = get_repr(&input, &args);
+            let repr = get_repr(input, args);
             let mut variants = TokStr::new();
 
-            iter_variants(e, &args, |v, discr| {
+            iter_variants(e, args, |v, discr| {
                 let args = MtArgs::from_variant(v).unwrap();
 
                 let (fields, fields_struct) = get_fields_struct(&v.fields);
+                let ident = &v.ident;
 
-                let mut code = serialize_fields(&fields);
-                serialize_args(&args, &mut code);
+                let path = format!("{typename}::{ident}");
+                let mut code = serialize_fields(&fields, &path, mode, args.version.is_some());
+                serialize_version(&args, &mut code, &path, mode);
+                serialize_args(&args, &mut code, mode);
 
-                let ident = &v.ident;
+                let write_discr =
+                    mode.call(quote! { &((#discr) as #repr) }, quote! { mt_ser::DefCfg });
 
                 variants.extend(quote! {
-					#typename::#ident #fields_struct => {
-						mt_ser::MtSerialize::mt_serialize::<mt_ser::DefCfg>(&((#discr) as #repr), __writer)?;
-						#code
-					}
-				});
+                    #typename::#ident #fields_struct => {
+                        #write_discr?;
+                        #code
+                    }
+                });
             });
 
             quote! {
@@ -573,54 +1377,128 @@ pub fn derive_serialize(input: TokenStream) -> TokenStream {
                 }
             }
         }
-        syn::Data::Struct(s) => serialize_fields(&get_fields(&s.fields, |f| quote! { &self.#f })),
+        syn::Data::Struct(s) => {
+            let path = typename.to_string();
+            let mut code = serialize_fields(
+                &get_fields(&s.fields, |f| quote! { &self.#f }),
+                &path,
+                mode,
+                args.version.is_some(),
+            );
+            serialize_version(args, &mut code, &path, mode);
+            code
+        }
         _ => {
             panic!("only enum and struct supported");
         }
     };
 
-    serialize_args(&args, &mut code);
+    serialize_args(args, &mut code, mode);
+
+    code
+}
+
+#[proc_macro_derive(MtSerialize, attributes(mt))]
+pub fn derive_serialize(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as syn::DeriveInput);
+    let args = MtArgs::from_derive_input(&input).unwrap();
+    let typename = args.typename.as_ref().unwrap_or(&input.ident);
+    let code = serialize_body(&input, &args, SerMode::Sync);
+    let len = serialize_body(&input, &args, SerMode::Len);
 
     make_impl(
         quote! { mt_ser::MtSerialize },
         &input,
+        &input.generics,
         typename,
         &args,
         quote! {
-            fn mt_serialize<C: mt_ser::MtCfg>(&self, __writer: &mut impl std::io::Write) -> Result<(), mt_ser::SerializeError> {
+            fn mt_serialize_with<C: mt_ser::MtCfg>(&self, __writer: &mut impl mt_ser::io::Write, __ctx: &mut mt_ser::SerializeCtx) -> Result<(), mt_ser::SerializeError> {
                 #code
 
                 Ok(())
             }
+
+            #[allow(unused_mut)]
+            fn mt_serialized_len_with<C: mt_ser::MtCfg>(&self, __ctx: &mut mt_ser::SerializeCtx) -> Result<Option<usize>, mt_ser::SerializeError> {
+                let mut __len = Some(0usize);
+                #len
+
+                Ok(__len)
+            }
         },
     )
 }
 
-#[proc_macro_derive(MtDeserialize, attributes(mt))]
-pub fn derive_deserialize(input: TokenStream) -> TokenStream {
+// zlib and zstd fields go through the async_compression encoders
+#[proc_macro_derive(MtAsyncSerialize, attributes(mt))]
+pub fn derive_serialize_async(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as syn::DeriveInput);
     let args = MtArgs::from_derive_input(&input).unwrap();
     let typename = args.typename.as_ref().unwrap_or(&input.ident);
+    let code = serialize_body(&input, &args, SerMode::Async);
+
+    make_impl(
+        quote! { mt_ser::MtAsyncSerialize },
+        &input,
+        &input.generics,
+        typename,
+        &args,
+        quote! {
+            async fn mt_serialize_async_with<
+                C: mt_ser::MtCfg,
+                W: mt_ser::tokio::io::AsyncWrite + Unpin + Send,
+            >(
+                &self,
+                __writer: &mut W,
+                __ctx: &mut mt_ser::SerializeCtx<'_>,
+            ) -> Result<(), mt_ser::SerializeError> {
+                #code
+
+                Ok(())
+            }
+        },
+    )
+}
+
+fn check_context_args(args: &MtArgs) {
+    if args.context.is_some() && args.map_ser.is_none() && args.map_des.is_none() {
+        panic!("context is only passed to map_ser and map_des");
+    }
+}
+
+fn check_borrowed_args(args: &MtArgs, mode: Mode) {
+    if mode == Mode::Borrowed && (args.zlib.is_some() || args.zstd.is_some()) {
+        panic!("zlib and zstd are only supported on fields for borrowed deserialization");
+    }
+}
+
+fn deserialize_body(input: &syn::DeriveInput, args: &MtArgs, mode: Mode) -> TokStr {
+    let typename = args.typename.as_ref().unwrap_or(&input.ident);
 
     let mut code = match &input.data {
         syn::Data::Enum(e) => {
-            let repr = get_repr(&input, &args);
+            let repr = get_repr(input, args);
 
             let mut consts = TokStr::new();
             let mut arms = TokStr::new();
 
-            iter_variants(e, &args, |v, discr| {
+            iter_variants(e, args, |v, discr| {
                 let args = MtArgs::from_variant(v).unwrap();
+                check_borrowed_args(&args, mode);
 
                 let ident = &v.ident;
                 let (fields, fields_struct) = get_fields_struct(&v.fields);
-                let mut code = deserialize_fields(&fields);
+                let path = format!("{typename}::{ident}");
+                let mut code = deserialize_fields(&fields, &path, mode, args.version.is_some());
                 code = quote! {
                     #code
                     Ok(Self::#ident #fields_struct)
                 };
 
-                deserialize_args(&args, &mut code);
+                deserialize_version(&args, &mut code, &path, mode);
+
+                deserialize_args(&args, &mut code, mode);
 
                 consts.extend(quote! {
                     const #ident: #repr = #discr;
@@ -632,14 +1510,15 @@ pub fn derive_deserialize(input: TokenStream) -> TokenStream {
             });
 
             let type_str = typename.to_string();
+            let read_discr = mode.owned().call(quote! { mt_ser::DefCfg });
             let discr_match = if args.string_repr {
                 quote! {
-                    let __discr = String::mt_deserialize::<DefCfg>(__reader)?;
+                    let __discr: mt_ser::__private::String = #read_discr?;
                     match __discr.as_str()
                 }
             } else {
                 quote! {
-                    let __discr = mt_ser::MtDeserialize::mt_deserialize::<DefCfg>(__reader)?;
+                    let __discr = #read_discr?;
                     match __discr
                 }
             };
@@ -649,36 +1528,374 @@ pub fn derive_deserialize(input: TokenStream) -> TokenStream {
 
                 #discr_match {
                     #arms
-                    _ => Err(mt_ser::DeserializeError::InvalidEnum(#type_str, Box::new(__discr)))
+                    _ => Err(mt_ser::DeserializeError::InvalidEnum(#type_str, mt_ser::__private::Box::new(__discr)))
                 }
             }
         }
         syn::Data::Struct(s) => {
             let (fields, fields_struct) = get_fields_struct(&s.fields);
-            let code = deserialize_fields(&fields);
-
-            quote! {
+            let path = typename.to_string();
+            let mut code = deserialize_fields(&fields, &path, mode, args.version.is_some());
+            code = quote! {
                 #code
                 Ok(Self #fields_struct)
-            }
+            };
+
+            deserialize_version(args, &mut code, &path, mode);
+            code
         }
         _ => {
             panic!("only enum and struct supported");
         }
     };
 
-    deserialize_args(&args, &mut code);
+    check_borrowed_args(args, mode);
+    deserialize_args(args, &mut code, mode);
+
+    code
+}
+
+#[proc_macro_derive(MtDeserialize, attributes(mt))]
+pub fn derive_deserialize(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as syn::DeriveInput);
+    let args = MtArgs::from_derive_input(&input).unwrap();
+    let typename = args.typename.as_ref().unwrap_or(&input.ident);
+    let code = deserialize_body(&input, &args, Mode::Owned);
 
     make_impl(
         quote! { mt_ser::MtDeserialize },
         &input,
+        &input.generics,
+        typename,
+        &args,
+        quote! {
+            #[allow(non_upper_case_globals, clippy::needless_question_mark)]
+            fn mt_deserialize_with<C: mt_ser::MtCfg>(
+                __reader: &mut impl mt_ser::io::Read,
+                __ctx: &mut mt_ser::DeserializeCtx,
+            ) -> Result<Self, mt_ser::DeserializeError> {
+                #code
+            }
+        },
+    )
+}
+
+#[proc_macro_derive(MtAsyncDeserialize, attributes(mt))]
+pub fn derive_deserialize_async(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as syn::DeriveInput);
+    let args = MtArgs::from_derive_input(&input).unwrap();
+    let typename = args.typename.as_ref().unwrap_or(&input.ident);
+    let code = deserialize_body(&input, &args, Mode::Async);
+
+    make_impl(
+        quote! { mt_ser::MtAsyncDeserialize },
+        &input,
+        &input.generics,
+        typename,
+        &args,
+        quote! {
+            #[allow(non_upper_case_globals, clippy::needless_question_mark)]
+            async fn mt_deserialize_async_with<
+                C: mt_ser::MtCfg,
+                R: mt_ser::tokio::io::AsyncRead + Unpin + Send,
+            >(
+                __reader: &mut R,
+                __ctx: &mut mt_ser::DeserializeCtx<'_>,
+            ) -> Result<Self, mt_ser::DeserializeError> {
+                #code
+            }
+        },
+    )
+}
+
+#[proc_macro_derive(MtDeserializeBorrowed, attributes(mt))]
+pub fn derive_deserialize_borrowed(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as syn::DeriveInput);
+    let args = MtArgs::from_derive_input(&input).unwrap();
+    let typename = args.typename.as_ref().unwrap_or(&input.ident);
+    let code = deserialize_body(&input, &args, Mode::Borrowed);
+
+    // '__de outlives every lifetime of the type so fields can borrow from the input
+    let mut de: syn::LifetimeDef = parse_quote! { '__de };
+    de.bounds
+        .extend(input.generics.lifetimes().map(|l| l.lifetime.clone()));
+
+    let mut impl_generics = input.generics.clone();
+    impl_generics
+        .params
+        .insert(0, syn::GenericParam::Lifetime(de));
+
+    make_impl(
+        quote! { mt_ser::MtDeserializeBorrowed<'__de> },
+        &input,
+        &impl_generics,
         typename,
         &args,
         quote! {
-            #[allow(non_upper_case_globals)]
-            fn mt_deserialize<C: mt_ser::MtCfg>(__reader: &mut impl std::io::Read) -> Result<Self, mt_ser::DeserializeError> {
+            #[allow(non_upper_case_globals, clippy::needless_question_mark)]
+            fn mt_deserialize_borrowed_with<C: mt_ser::MtCfg>(
+                __reader: &mut &'__de [u8],
+                __ctx: &mut mt_ser::DeserializeCtx,
+            ) -> Result<Self, mt_ser::DeserializeError> {
                 #code
             }
         },
     )
 }
+
+// whether MtStrategy and GenerateRandom make up a value for a field. fields without a value
+// at the newest version (or at max_version, the one serialized for versioned structs and
+// variants) deserialize to Default, and mapped fields may not roundtrip, so these are
+// left at their default
+fn field_generated(field_args: &MtArgs, args: &MtArgs) -> bool {
+    let present = field_args.until.is_none()
+        && args.max_version.is_none_or(|v| {
+            field_args.min_version.is_none_or(|min| min <= v)
+                && field_args.max_version.is_none_or(|max| v <= max)
+        });
+    let mapped = field_args.map_ser.is_some()
+        || field_args.map_des.is_some()
+        || field_args.multiplier.is_some();
+
+    present && !mapped
+}
+
+// whether more data follows the contents of something with these args, given whether more
+// data follows the thing itself. size and compression end the input of their contents
+fn followed_inside(args: &MtArgs, followed: bool) -> bool {
+    args.size.is_none()
+        && args.zlib.is_none()
+        && args.zstd.is_none()
+        && (followed || !args.const_after.is_empty())
+}
+
+// the strategy of each field for the len of that field, as nested pairs so that there is no
+// limit on the number of fields. only fields at the end of the input may produce a None
+fn strategy_fields(
+    fields: &Fields,
+    args: &MtArgs,
+    fields_struct: TokStr,
+    followed: bool,
+) -> TokStr {
+    let mut strategies = quote! { mt_ser::proptest::strategy::Just(()) };
+    let mut pattern = quote! { () };
+
+    for (i, (ident, _, field)) in fields.iter().enumerate().rev() {
+        let field_args = MtArgs::from_field(field).unwrap();
+        let method = if followed_inside(&field_args, followed || i + 1 < fields.len()) {
+            quote! { mt_strategy_followed }
+        } else {
+            quote! { mt_strategy }
+        };
+        let def = parse_quote! { mt_ser::DefCfg };
+        let len = field_args.len.as_ref().unwrap_or(&def);
+        let ty = &field.ty;
+
+        let strategy = if let Some(n) = field_args.bits {
+            match check_bits(field, n) {
+                Ok(()) => quote! { mt_ser::mt_strategy_bits::<#ty>(#n) },
+                Err(e) => e,
+            }
+        } else if field_generated(&field_args, args) {
+            quote! { <#ty as mt_ser::MtStrategy>::#method::<#len>() }
+        } else {
+            quote! { mt_ser::mt_strategy_default::<#ty>() }
+        };
+
+        strategies = quote! { (#strategy, #strategies) };
+        pattern = quote! { (#ident, #pattern) };
+    }
+
+    quote! {
+        mt_ser::proptest::strategy::Strategy::boxed(
+            mt_ser::proptest::strategy::Strategy::prop_map(
+                #strategies,
+                |#pattern| #fields_struct,
+            )
+        )
+    }
+}
+
+fn strategy_body(input: &syn::DeriveInput, args: &MtArgs, followed: bool) -> TokStr {
+    let followed = followed_inside(args, followed);
+
+    match &input.data {
+        syn::Data::Enum(e) => {
+            let mut variants = TokStr::new();
+
+            iter_variants(e, args, |v, _| {
+                let args = MtArgs::from_variant(v).unwrap();
+                let ident = &v.ident;
+                let (fields, fields_struct) = get_fields_struct(&v.fields);
+                let code = strategy_fields(
+                    &fields,
+                    &args,
+                    quote! { Self::#ident #fields_struct },
+                    followed_inside(&args, followed),
+                );
+
+                variants.extend(quote! { #code, });
+            });
+
+            quote! {
+                mt_ser::proptest::strategy::Strategy::boxed(
+                    mt_ser::proptest::strategy::Union::new([#variants])
+                )
+            }
+        }
+        syn::Data::Struct(s) => {
+            let (fields, fields_struct) = get_fields_struct(&s.fields);
+            strategy_fields(&fields, args, quote! { Self #fields_struct }, followed)
+        }
+        _ => {
+            panic!("only enum and struct supported");
+        }
+    }
+}
+
+#[proc_macro_derive(MtStrategy, attributes(mt))]
+pub fn derive_strategy(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as syn::DeriveInput);
+    let args = MtArgs::from_derive_input(&input).unwrap();
+    let typename = args.typename.as_ref().unwrap_or(&input.ident);
+
+    let code = strategy_body(&input, &args, false);
+    let code_followed = strategy_body(&input, &args, true);
+
+    make_impl(
+        quote! { mt_ser::MtStrategy },
+        &input,
+        &input.generics,
+        typename,
+        &args,
+        quote! {
+            fn mt_strategy<C: mt_ser::MtCfg>() -> mt_ser::proptest::strategy::BoxedStrategy<Self> {
+                #code
+            }
+
+            fn mt_strategy_followed<C: mt_ser::MtCfg>(
+            ) -> mt_ser::proptest::strategy::BoxedStrategy<Self> {
+                #code_followed
+            }
+        },
+    )
+}
+
+// like strategy_fields, only fields at the end of the input may generate a None
+fn random_fields(fields: &Fields, args: &MtArgs, followed: bool) -> TokStr {
+    fields
+        .iter()
+        .enumerate()
+        .map(|(i, (ident, _, field))| {
+            let field_args = MtArgs::from_field(field).unwrap();
+            let method = if followed_inside(&field_args, followed || i + 1 < fields.len()) {
+                quote! { generate_random_followed }
+            } else {
+                quote! { generate_random }
+            };
+            let def = parse_quote! { mt_ser::DefCfg };
+            let len = field_args.len.as_ref().unwrap_or(&def);
+            let ty = &field.ty;
+
+            if let Some(n) = field_args.bits {
+                match check_bits(field, n) {
+                    Ok(()) => quote! {
+                        let #ident = mt_ser::mt_generate_bits::<#ty>(__rng, #n);
+                    },
+                    Err(e) => e,
+                }
+            } else if field_generated(&field_args, args) {
+                quote! {
+                    let #ident = <#ty as mt_ser::GenerateRandom>::#method::<#len>(__rng);
+                }
+            } else {
+                quote! {
+                    let #ident = <#ty as Default>::default();
+                }
+            }
+        })
+        .collect()
+}
+
+fn random_body(input: &syn::DeriveInput, args: &MtArgs, followed: bool) -> TokStr {
+    let followed = followed_inside(args, followed);
+
+    match &input.data {
+        syn::Data::Enum(e) => {
+            let mut arms = TokStr::new();
+            let mut count = 0usize;
+
+            iter_variants(e, args, |v, _| {
+                let args = MtArgs::from_variant(v).unwrap();
+                let ident = &v.ident;
+                let (fields, fields_struct) = get_fields_struct(&v.fields);
+                let code = random_fields(&fields, &args, followed_inside(&args, followed));
+
+                arms.extend(quote! {
+                    #count => {
+                        #code
+                        Self::#ident #fields_struct
+                    }
+                });
+                count += 1;
+            });
+
+            quote! {
+                match mt_ser::rand::Rng::gen_range(__rng, 0..#count) {
+                    #arms
+                    _ => unreachable!(),
+                }
+            }
+        }
+        syn::Data::Struct(s) => {
+            let (fields, fields_struct) = get_fields_struct(&s.fields);
+            let code = random_fields(&fields, args, followed);
+
+            quote! {
+                #code
+                Self #fields_struct
+            }
+        }
+        _ => {
+            panic!("only enum and struct supported");
+        }
+    }
+}
+
+#[proc_macro_derive(GenerateRandom, attributes(mt))]
+pub fn derive_generate_random(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as syn::DeriveInput);
+    let args = MtArgs::from_derive_input(&input).unwrap();
+    let typename = args.typename.as_ref().unwrap_or(&input.ident);
+
+    if let syn::Data::Enum(e) = &input.data {
+        if e.variants.is_empty() {
+            return TokenStream::from(
+                syn::Error::new_spanned(&input.ident, "can not generate a value of an empty enum")
+                    .to_compile_error(),
+            );
+        }
+    }
+
+    let code = random_body(&input, &args, false);
+    let code_followed = random_body(&input, &args, true);
+
+    make_impl(
+        quote! { mt_ser::GenerateRandom },
+        &input,
+        &input.generics,
+        typename,
+        &args,
+        quote! {
+            fn generate_random<C: mt_ser::MtCfg>(__rng: &mut impl mt_ser::rand::Rng) -> Self {
+                #code
+            }
+
+            fn generate_random_followed<C: mt_ser::MtCfg>(
+                __rng: &mut impl mt_ser::rand::Rng,
+            ) -> Self {
+                #code_followed
+            }
+        },
+    )
+}
@@ -0,0 +1,11 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use mt_ser_fuzz::TYPES;
+
+// the first byte picks the type, the rest is its input
+fuzz_target!(|data: &[u8]| {
+    if let Some((&index, data)) = data.split_first() {
+        TYPES[index as usize % TYPES.len()](data);
+    }
+});
@@ -0,0 +1,125 @@
+use arbitrary::{Arbitrary, Unstructured};
+use mt_ser::{DefCfg, DeserializeError, Limits, MtDeserialize, MtSerialize};
+use std::fmt::Debug;
+
+// types checked by the fuzz targets, add new ones here
+pub const TYPES: &[fn(&[u8])] = &[
+    check::<Plain>,
+    check::<Lengths>,
+    check::<Sections>,
+    check::<Compressed>,
+    check::<Trailing>,
+    check::<Versioned>,
+    check::<Packet>,
+];
+
+#[derive(Debug, PartialEq, Arbitrary, MtSerialize, MtDeserialize)]
+pub struct Plain {
+    pub a: u8,
+    pub b: i16,
+    pub c: u32,
+    pub d: bool,
+    pub e: (u16, i64),
+}
+
+#[derive(Debug, PartialEq, Arbitrary, MtSerialize, MtDeserialize)]
+pub struct Lengths {
+    #[mt(len = "u8")]
+    pub short: Vec<u8>,
+    pub name: String,
+    #[mt(len = "mt_ser::Utf16")]
+    pub utf16: String,
+    #[mt(len = "u32")]
+    pub names: Vec<String>,
+}
+
+#[derive(Debug, PartialEq, Arbitrary, MtSerialize, MtDeserialize)]
+pub struct Sections {
+    #[mt(size = "u16")]
+    pub inner: Lengths,
+    #[mt(size = "u32", len = "()")]
+    pub rest: Vec<u16>,
+    pub after: u8,
+}
+
+#[derive(Debug, PartialEq, Arbitrary, MtSerialize, MtDeserialize)]
+pub struct Compressed {
+    #[mt(size = "u32", zlib)]
+    pub zlib: Lengths,
+    #[mt(size = "u32", zstd)]
+    pub zstd: Vec<u8>,
+    #[mt(size = "u16", zlib(level = 9))]
+    pub best: Vec<u16>,
+}
+
+// compressed sections without size read to the end, so this is never nested
+#[derive(Debug, PartialEq, Arbitrary, MtSerialize, MtDeserialize)]
+pub struct Trailing {
+    pub a: u8,
+    #[mt(zstd)]
+    pub rest: Vec<u8>,
+}
+
+#[derive(Debug, PartialEq, Arbitrary, MtSerialize, MtDeserialize)]
+#[mt(version = "u8", min_version = 1, max_version = 3)]
+pub struct Versioned {
+    pub a: u16,
+    #[mt(min_version = 2)]
+    pub b: Vec<u8>,
+    #[mt(min_version = 3)]
+    pub c: String,
+}
+
+#[derive(Debug, PartialEq, Arbitrary, MtSerialize, MtDeserialize)]
+#[repr(u8)]
+pub enum Packet {
+    Empty,
+    Plain(Plain),
+    #[mt(const_before = "7u8")]
+    Sections(Sections),
+    Compressed(Box<Compressed>),
+    Nested(#[mt(len = "u8")] Vec<Packet>),
+}
+
+// bounds hostile length prefixes and compressed sections
+fn limits() -> Limits {
+    let mut limits = Limits::new(1 << 16, 1 << 24, 64);
+    limits.max_decompressed = 1 << 24;
+    limits
+}
+
+fn decode<T: MtDeserialize>(mut data: &[u8]) -> Result<T, DeserializeError> {
+    T::mt_deserialize_limited::<DefCfg>(&mut data, &mut limits())
+}
+
+// decode -> encode -> decode must give the same bytes again
+fn roundtrip<T: MtSerialize + MtDeserialize + Debug>(item: &T) {
+    let mut bytes = Vec::new();
+    // e.g. more elements than the len config allows
+    if item.mt_serialize::<DefCfg>(&mut bytes).is_err() {
+        return;
+    }
+
+    let again: T = decode(&bytes)
+        .unwrap_or_else(|err| panic!("decoding {item:?} from {bytes:02x?} failed: {err}"));
+
+    let mut again_bytes = Vec::new();
+    again.mt_serialize::<DefCfg>(&mut again_bytes).unwrap();
+    assert_eq!(bytes, again_bytes, "{item:?} changed to {again:?}");
+}
+
+pub fn check<T>(data: &[u8])
+where
+    T: MtSerialize + MtDeserialize + for<'a> Arbitrary<'a> + Debug,
+{
+    // random bytes may fail to decode but must not panic
+    if let Ok(item) = decode::<T>(data) {
+        roundtrip(&item);
+    }
+
+    // random bytes rarely make a valid zlib/zstd stream or size prefix,
+    // structured values reach the compressed and sized sections
+    if let Ok(item) = T::arbitrary(&mut Unstructured::new(data)) {
+        roundtrip(&item);
+    }
+}
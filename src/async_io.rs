@@ -0,0 +1,699 @@
+use super::*;
+use core::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+use paste::paste;
+use std::future::Future;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+// implementors provide at least one of the two methods, like with MtSerialize
+pub trait MtAsyncSerialize: Sync {
+    fn mt_serialize_async<C: MtCfg, W: AsyncWrite + Unpin + Send>(
+        &self,
+        writer: &mut W,
+    ) -> impl Future<Output = Result<(), SerializeError>> + Send {
+        async move {
+            self.mt_serialize_async_with::<C, W>(writer, &mut SerializeCtx::default())
+                .await
+        }
+    }
+
+    fn mt_serialize_async_with<C: MtCfg, W: AsyncWrite + Unpin + Send>(
+        &self,
+        writer: &mut W,
+        ctx: &mut SerializeCtx,
+    ) -> impl Future<Output = Result<(), SerializeError>> + Send {
+        let _ = ctx;
+        self.mt_serialize_async::<C, W>(writer)
+    }
+}
+
+// implementors provide at least one of the two methods, like with MtDeserialize
+pub trait MtAsyncDeserialize: Sized + Send {
+    fn mt_deserialize_async<C: MtCfg, R: AsyncRead + Unpin + Send>(
+        reader: &mut R,
+    ) -> impl Future<Output = Result<Self, DeserializeError>> + Send {
+        async move {
+            Self::mt_deserialize_async_with::<C, R>(reader, &mut DeserializeCtx::default()).await
+        }
+    }
+
+    fn mt_deserialize_async_with<C: MtCfg, R: AsyncRead + Unpin + Send>(
+        reader: &mut R,
+        ctx: &mut DeserializeCtx,
+    ) -> impl Future<Output = Result<Self, DeserializeError>> + Send {
+        let _ = ctx;
+        Self::mt_deserialize_async::<C, R>(reader)
+    }
+}
+
+// finishing an async_compression encoder shuts down the writer it wraps,
+// this only flushes instead so that the rest of the message can follow
+pub struct KeepOpen<'a, W: AsyncWrite + Unpin>(pub &'a mut W);
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for KeepOpen<'_, W> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut *self.0).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut *self.0).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut *self.0).poll_flush(cx)
+    }
+}
+
+// lengths are encoded by MtCfg::write_len and then written in one go
+async fn write_len_async<C: MtCfg, W: AsyncWrite + Unpin + Send>(
+    len: usize,
+    writer: &mut W,
+) -> Result<(), SerializeError> {
+    let mut buf = Vec::new();
+    C::write_len(len, &mut buf)?;
+    writer.write_all(&buf).await?;
+
+    Ok(())
+}
+
+pub async fn mt_serialize_seq_async<C, T, W>(
+    writer: &mut W,
+    iter: impl ExactSizeIterator<Item = T> + Send,
+    ctx: &mut SerializeCtx<'_>,
+) -> Result<(), SerializeError>
+where
+    C: MtCfg,
+    T: MtAsyncSerialize + Send,
+    W: AsyncWrite + Unpin + Send,
+{
+    write_len_async::<C, _>(iter.len(), writer).await?;
+
+    for (i, item) in iter.enumerate() {
+        // boxing the future allows recursive types
+        Box::pin(item.mt_serialize_async_with::<C::Inner, _>(writer, ctx))
+            .await
+            .map_err(|e| e.at_index(i))?;
+    }
+
+    Ok(())
+}
+
+// unlike the trait method, the return type of this lets the element type be
+// inferred from how the result is used, like with MtDeserialize::mt_deserialize
+pub async fn mt_deserialize_async<T, C, R>(reader: &mut R) -> Result<T, DeserializeError>
+where
+    T: MtAsyncDeserialize,
+    C: MtCfg,
+    R: AsyncRead + Unpin + Send,
+{
+    T::mt_deserialize_async::<C, R>(reader).await
+}
+
+pub async fn mt_deserialize_async_with<T, C, R>(
+    reader: &mut R,
+    ctx: &mut DeserializeCtx<'_>,
+) -> Result<T, DeserializeError>
+where
+    T: MtAsyncDeserialize,
+    C: MtCfg,
+    R: AsyncRead + Unpin + Send,
+{
+    T::mt_deserialize_async_with::<C, R>(reader, ctx).await
+}
+
+// like DeserializeCtx::nested, for a future
+async fn nested_async<T: MtAsyncDeserialize, C: MtCfg, R: AsyncRead + Unpin + Send>(
+    reader: &mut R,
+    ctx: &mut DeserializeCtx<'_>,
+) -> Result<T, DeserializeError> {
+    ctx.limits.enter()?;
+    // boxing the future allows recursive types
+    let res = Box::pin(T::mt_deserialize_async_with::<C, _>(reader, ctx)).await;
+    ctx.limits.leave();
+
+    res
+}
+
+// applies the same limits as mt_deserialize_sized_seq_with
+pub async fn mt_deserialize_seq_async<C, T, B, R>(
+    reader: &mut R,
+    ctx: &mut DeserializeCtx<'_>,
+) -> Result<B, DeserializeError>
+where
+    C: MtCfg,
+    T: MtAsyncDeserialize,
+    B: Default + Extend<T>,
+    R: AsyncRead + Unpin + Send,
+{
+    let len = C::read_len_async(reader).await?;
+    let mut seq = B::default();
+
+    if let Some(x) = len {
+        ctx.limits.check_len(x)?;
+    }
+
+    for i in 0..len.unwrap_or(usize::MAX) {
+        let item = match ctx
+            .limits
+            .check_len(i + 1)
+            .and_then(|_| ctx.limits.alloc(core::mem::size_of::<T>()))
+        {
+            Ok(()) => nested_async::<T, C::Inner, _>(reader, ctx).await,
+            Err(e) => Err(e),
+        };
+
+        match seq_item(len.is_none(), i, item) {
+            Some(item) => seq.extend(Some(item?)),
+            None => break,
+        }
+    }
+
+    Ok(seq)
+}
+
+impl MtAsyncSerialize for u8 {
+    async fn mt_serialize_async<C: MtCfg, W: AsyncWrite + Unpin + Send>(
+        &self,
+        writer: &mut W,
+    ) -> Result<(), SerializeError> {
+        Ok(writer.write_u8(*self).await?)
+    }
+}
+
+impl MtAsyncDeserialize for u8 {
+    async fn mt_deserialize_async<C: MtCfg, R: AsyncRead + Unpin + Send>(
+        reader: &mut R,
+    ) -> Result<Self, DeserializeError> {
+        Ok(reader.read_u8().await?)
+    }
+}
+
+impl MtAsyncSerialize for i8 {
+    async fn mt_serialize_async<C: MtCfg, W: AsyncWrite + Unpin + Send>(
+        &self,
+        writer: &mut W,
+    ) -> Result<(), SerializeError> {
+        Ok(writer.write_i8(*self).await?)
+    }
+}
+
+impl MtAsyncDeserialize for i8 {
+    async fn mt_deserialize_async<C: MtCfg, R: AsyncRead + Unpin + Send>(
+        reader: &mut R,
+    ) -> Result<Self, DeserializeError> {
+        Ok(reader.read_i8().await?)
+    }
+}
+
+// tokio's read_* and write_* helpers are big endian already
+macro_rules! impl_num {
+    ($T:ty) => {
+        impl MtAsyncSerialize for $T {
+            async fn mt_serialize_async<C: MtCfg, W: AsyncWrite + Unpin + Send>(
+                &self,
+                writer: &mut W,
+            ) -> Result<(), SerializeError> {
+                paste! {
+                    Ok(writer.[<write_ $T>](*self).await?)
+                }
+            }
+        }
+
+        impl MtAsyncDeserialize for $T {
+            async fn mt_deserialize_async<C: MtCfg, R: AsyncRead + Unpin + Send>(
+                reader: &mut R,
+            ) -> Result<Self, DeserializeError> {
+                paste! {
+                    Ok(reader.[<read_ $T>]().await?)
+                }
+            }
+        }
+    };
+}
+
+impl_num!(u16);
+impl_num!(i16);
+
+impl_num!(u32);
+impl_num!(i32);
+impl_num!(f32);
+
+impl_num!(u64);
+impl_num!(i64);
+impl_num!(f64);
+
+impl MtAsyncSerialize for () {
+    async fn mt_serialize_async<C: MtCfg, W: AsyncWrite + Unpin + Send>(
+        &self,
+        _writer: &mut W,
+    ) -> Result<(), SerializeError> {
+        Ok(())
+    }
+}
+
+impl MtAsyncDeserialize for () {
+    async fn mt_deserialize_async<C: MtCfg, R: AsyncRead + Unpin + Send>(
+        _reader: &mut R,
+    ) -> Result<Self, DeserializeError> {
+        Ok(())
+    }
+}
+
+impl MtAsyncSerialize for bool {
+    async fn mt_serialize_async<C: MtCfg, W: AsyncWrite + Unpin + Send>(
+        &self,
+        writer: &mut W,
+    ) -> Result<(), SerializeError> {
+        (*self as u8).mt_serialize_async::<DefCfg, _>(writer).await
+    }
+}
+
+impl MtAsyncDeserialize for bool {
+    async fn mt_deserialize_async<C: MtCfg, R: AsyncRead + Unpin + Send>(
+        reader: &mut R,
+    ) -> Result<Self, DeserializeError> {
+        Ok(u8::mt_deserialize_async::<DefCfg, _>(reader).await? != 0)
+    }
+}
+
+impl<T: MtAsyncSerialize + ?Sized> MtAsyncSerialize for &T {
+    async fn mt_serialize_async_with<C: MtCfg, W: AsyncWrite + Unpin + Send>(
+        &self,
+        writer: &mut W,
+        ctx: &mut SerializeCtx<'_>,
+    ) -> Result<(), SerializeError> {
+        (*self).mt_serialize_async_with::<C, _>(writer, ctx).await
+    }
+}
+
+impl<T: MtAsyncSerialize, const N: usize> MtAsyncSerialize for [T; N] {
+    async fn mt_serialize_async_with<C: MtCfg, W: AsyncWrite + Unpin + Send>(
+        &self,
+        writer: &mut W,
+        ctx: &mut SerializeCtx<'_>,
+    ) -> Result<(), SerializeError> {
+        mt_serialize_seq_async::<(), _, _>(writer, self.iter(), ctx).await
+    }
+}
+
+impl<T: MtAsyncDeserialize, const N: usize> MtAsyncDeserialize for [T; N] {
+    async fn mt_deserialize_async_with<C: MtCfg, R: AsyncRead + Unpin + Send>(
+        reader: &mut R,
+        ctx: &mut DeserializeCtx<'_>,
+    ) -> Result<Self, DeserializeError> {
+        let mut items = Vec::with_capacity(N);
+
+        for i in 0..N {
+            let item = T::mt_deserialize_async_with::<DefCfg, _>(reader, ctx).await;
+            items.push(item.map_err(|e| e.at_index(i))?);
+        }
+
+        Ok(items
+            .try_into()
+            .unwrap_or_else(|_| unreachable!("array length")))
+    }
+}
+
+impl<T, E> MtAsyncSerialize for EnumSet<E>
+where
+    T: MtAsyncSerialize + Send,
+    E: EnumSetTypeWithRepr<Repr = T> + Sync,
+{
+    async fn mt_serialize_async<C: MtCfg, W: AsyncWrite + Unpin + Send>(
+        &self,
+        writer: &mut W,
+    ) -> Result<(), SerializeError> {
+        self.as_repr().mt_serialize_async::<DefCfg, _>(writer).await
+    }
+}
+
+impl<T, E> MtAsyncDeserialize for EnumSet<E>
+where
+    T: MtAsyncDeserialize,
+    E: EnumSetTypeWithRepr<Repr = T> + Send,
+{
+    async fn mt_deserialize_async<C: MtCfg, R: AsyncRead + Unpin + Send>(
+        reader: &mut R,
+    ) -> Result<Self, DeserializeError> {
+        Ok(Self::from_repr_truncated(
+            T::mt_deserialize_async::<DefCfg, _>(reader).await?,
+        ))
+    }
+}
+
+impl<T: MtAsyncSerialize> MtAsyncSerialize for Option<T> {
+    async fn mt_serialize_async_with<C: MtCfg, W: AsyncWrite + Unpin + Send>(
+        &self,
+        writer: &mut W,
+        ctx: &mut SerializeCtx<'_>,
+    ) -> Result<(), SerializeError> {
+        match self {
+            Some(item) => item.mt_serialize_async_with::<C, _>(writer, ctx).await,
+            None => Ok(()),
+        }
+    }
+}
+
+impl<T: MtAsyncDeserialize> MtAsyncDeserialize for Option<T> {
+    async fn mt_deserialize_async_with<C: MtCfg, R: AsyncRead + Unpin + Send>(
+        reader: &mut R,
+        ctx: &mut DeserializeCtx<'_>,
+    ) -> Result<Self, DeserializeError> {
+        match T::mt_deserialize_async_with::<C, _>(reader, ctx).await {
+            Err(e) if matches!(e.inner(), DeserializeError::UnexpectedEof) => Ok(None),
+            x => x.map(Some),
+        }
+    }
+}
+
+impl<T: MtAsyncSerialize> MtAsyncSerialize for [T] {
+    async fn mt_serialize_async_with<C: MtCfg, W: AsyncWrite + Unpin + Send>(
+        &self,
+        writer: &mut W,
+        ctx: &mut SerializeCtx<'_>,
+    ) -> Result<(), SerializeError> {
+        mt_serialize_seq_async::<C, _, _>(writer, self.iter(), ctx).await
+    }
+}
+
+impl<T: MtAsyncSerialize> MtAsyncSerialize for Vec<T> {
+    async fn mt_serialize_async_with<C: MtCfg, W: AsyncWrite + Unpin + Send>(
+        &self,
+        writer: &mut W,
+        ctx: &mut SerializeCtx<'_>,
+    ) -> Result<(), SerializeError> {
+        mt_serialize_seq_async::<C, _, _>(writer, self.iter(), ctx).await
+    }
+}
+
+impl<T: MtAsyncDeserialize> MtAsyncDeserialize for Vec<T> {
+    async fn mt_deserialize_async_with<C: MtCfg, R: AsyncRead + Unpin + Send>(
+        reader: &mut R,
+        ctx: &mut DeserializeCtx<'_>,
+    ) -> Result<Self, DeserializeError> {
+        mt_deserialize_seq_async::<C, _, _, _>(reader, ctx).await
+    }
+}
+
+impl<T: MtAsyncSerialize> MtAsyncSerialize for VecDeque<T> {
+    async fn mt_serialize_async_with<C: MtCfg, W: AsyncWrite + Unpin + Send>(
+        &self,
+        writer: &mut W,
+        ctx: &mut SerializeCtx<'_>,
+    ) -> Result<(), SerializeError> {
+        mt_serialize_seq_async::<C, _, _>(writer, self.iter(), ctx).await
+    }
+}
+
+impl<T: MtAsyncDeserialize> MtAsyncDeserialize for VecDeque<T> {
+    async fn mt_deserialize_async_with<C: MtCfg, R: AsyncRead + Unpin + Send>(
+        reader: &mut R,
+        ctx: &mut DeserializeCtx<'_>,
+    ) -> Result<Self, DeserializeError> {
+        mt_deserialize_seq_async::<C, _, _, _>(reader, ctx).await
+    }
+}
+
+impl<T: MtAsyncSerialize + Eq + Hash, S: Sync> MtAsyncSerialize for HashSet<T, S> {
+    async fn mt_serialize_async_with<C: MtCfg, W: AsyncWrite + Unpin + Send>(
+        &self,
+        writer: &mut W,
+        ctx: &mut SerializeCtx<'_>,
+    ) -> Result<(), SerializeError> {
+        mt_serialize_seq_async::<C, _, _>(writer, self.iter(), ctx).await
+    }
+}
+
+impl<T, S> MtAsyncDeserialize for HashSet<T, S>
+where
+    T: MtAsyncDeserialize + Eq + Hash,
+    S: BuildHasher + Default + Send,
+{
+    async fn mt_deserialize_async_with<C: MtCfg, R: AsyncRead + Unpin + Send>(
+        reader: &mut R,
+        ctx: &mut DeserializeCtx<'_>,
+    ) -> Result<Self, DeserializeError> {
+        mt_deserialize_seq_async::<C, _, _, _>(reader, ctx).await
+    }
+}
+
+impl<T: MtAsyncSerialize + Ord> MtAsyncSerialize for BTreeSet<T> {
+    async fn mt_serialize_async_with<C: MtCfg, W: AsyncWrite + Unpin + Send>(
+        &self,
+        writer: &mut W,
+        ctx: &mut SerializeCtx<'_>,
+    ) -> Result<(), SerializeError> {
+        mt_serialize_seq_async::<C, _, _>(writer, self.iter(), ctx).await
+    }
+}
+
+impl<T: MtAsyncDeserialize + Ord> MtAsyncDeserialize for BTreeSet<T> {
+    async fn mt_deserialize_async_with<C: MtCfg, R: AsyncRead + Unpin + Send>(
+        reader: &mut R,
+        ctx: &mut DeserializeCtx<'_>,
+    ) -> Result<Self, DeserializeError> {
+        mt_deserialize_seq_async::<C, _, _, _>(reader, ctx).await
+    }
+}
+
+#[cfg(feature = "indexmap")]
+impl<T: MtAsyncSerialize + Eq + Hash, S: Sync> MtAsyncSerialize for IndexSet<T, S> {
+    async fn mt_serialize_async_with<C: MtCfg, W: AsyncWrite + Unpin + Send>(
+        &self,
+        writer: &mut W,
+        ctx: &mut SerializeCtx<'_>,
+    ) -> Result<(), SerializeError> {
+        mt_serialize_seq_async::<C, _, _>(writer, self.iter(), ctx).await
+    }
+}
+
+#[cfg(feature = "indexmap")]
+impl<T, S> MtAsyncDeserialize for IndexSet<T, S>
+where
+    T: MtAsyncDeserialize + Eq + Hash,
+    S: BuildHasher + Default + Send,
+{
+    async fn mt_deserialize_async_with<C: MtCfg, R: AsyncRead + Unpin + Send>(
+        reader: &mut R,
+        ctx: &mut DeserializeCtx<'_>,
+    ) -> Result<Self, DeserializeError> {
+        mt_deserialize_seq_async::<C, _, _, _>(reader, ctx).await
+    }
+}
+
+impl<K, V, S> MtAsyncSerialize for HashMap<K, V, S>
+where
+    K: MtAsyncSerialize + Eq + Hash,
+    V: MtAsyncSerialize,
+    S: Sync,
+{
+    async fn mt_serialize_async_with<C: MtCfg, W: AsyncWrite + Unpin + Send>(
+        &self,
+        writer: &mut W,
+        ctx: &mut SerializeCtx<'_>,
+    ) -> Result<(), SerializeError> {
+        mt_serialize_seq_async::<C, _, _>(writer, self.iter(), ctx).await
+    }
+}
+
+impl<K, V, S> MtAsyncDeserialize for HashMap<K, V, S>
+where
+    K: MtAsyncDeserialize + Eq + Hash,
+    V: MtAsyncDeserialize,
+    S: BuildHasher + Default + Send,
+{
+    async fn mt_deserialize_async_with<C: MtCfg, R: AsyncRead + Unpin + Send>(
+        reader: &mut R,
+        ctx: &mut DeserializeCtx<'_>,
+    ) -> Result<Self, DeserializeError> {
+        mt_deserialize_seq_async::<C, _, _, _>(reader, ctx).await
+    }
+}
+
+impl<K: MtAsyncSerialize + Ord, V: MtAsyncSerialize> MtAsyncSerialize for BTreeMap<K, V> {
+    async fn mt_serialize_async_with<C: MtCfg, W: AsyncWrite + Unpin + Send>(
+        &self,
+        writer: &mut W,
+        ctx: &mut SerializeCtx<'_>,
+    ) -> Result<(), SerializeError> {
+        mt_serialize_seq_async::<C, _, _>(writer, self.iter(), ctx).await
+    }
+}
+
+impl<K, V> MtAsyncDeserialize for BTreeMap<K, V>
+where
+    K: MtAsyncDeserialize + Ord,
+    V: MtAsyncDeserialize,
+{
+    async fn mt_deserialize_async_with<C: MtCfg, R: AsyncRead + Unpin + Send>(
+        reader: &mut R,
+        ctx: &mut DeserializeCtx<'_>,
+    ) -> Result<Self, DeserializeError> {
+        mt_deserialize_seq_async::<C, _, _, _>(reader, ctx).await
+    }
+}
+
+#[cfg(feature = "indexmap")]
+impl<K, V, S> MtAsyncSerialize for IndexMap<K, V, S>
+where
+    K: MtAsyncSerialize + Eq + Hash,
+    V: MtAsyncSerialize,
+    S: Sync,
+{
+    async fn mt_serialize_async_with<C: MtCfg, W: AsyncWrite + Unpin + Send>(
+        &self,
+        writer: &mut W,
+        ctx: &mut SerializeCtx<'_>,
+    ) -> Result<(), SerializeError> {
+        mt_serialize_seq_async::<C, _, _>(writer, self.iter(), ctx).await
+    }
+}
+
+#[cfg(feature = "indexmap")]
+impl<K, V, S> MtAsyncDeserialize for IndexMap<K, V, S>
+where
+    K: MtAsyncDeserialize + Eq + Hash,
+    V: MtAsyncDeserialize,
+    S: BuildHasher + Default + Send,
+{
+    async fn mt_deserialize_async_with<C: MtCfg, R: AsyncRead + Unpin + Send>(
+        reader: &mut R,
+        ctx: &mut DeserializeCtx<'_>,
+    ) -> Result<Self, DeserializeError> {
+        mt_deserialize_seq_async::<C, _, _, _>(reader, ctx).await
+    }
+}
+
+impl MtAsyncSerialize for str {
+    async fn mt_serialize_async<C: MtCfg, W: AsyncWrite + Unpin + Send>(
+        &self,
+        writer: &mut W,
+    ) -> Result<(), SerializeError> {
+        if C::utf16() {
+            let units: Vec<u16> = self.encode_utf16().collect();
+            units.mt_serialize_async::<C, _>(writer).await
+        } else {
+            write_len_async::<C, _>(self.len(), writer).await?;
+            Ok(writer.write_all(self.as_bytes()).await?)
+        }
+    }
+}
+
+impl MtAsyncSerialize for String {
+    async fn mt_serialize_async<C: MtCfg, W: AsyncWrite + Unpin + Send>(
+        &self,
+        writer: &mut W,
+    ) -> Result<(), SerializeError> {
+        self.as_str().mt_serialize_async::<C, _>(writer).await
+    }
+}
+
+impl MtAsyncDeserialize for String {
+    async fn mt_deserialize_async_with<C: MtCfg, R: AsyncRead + Unpin + Send>(
+        reader: &mut R,
+        ctx: &mut DeserializeCtx<'_>,
+    ) -> Result<Self, DeserializeError> {
+        if C::utf16() {
+            let units: Vec<u16> = mt_deserialize_seq_async::<C, u16, _, _>(reader, ctx).await?;
+            Ok(char::decode_utf16(units).collect::<Result<_, _>>()?)
+        } else {
+            let mut buf = Vec::new();
+
+            match C::read_len_async(reader).await? {
+                Some(len) => {
+                    ctx.limits.check_len(len)?;
+                    ctx.limits.alloc(len)?;
+                    reader.take(len as u64).read_to_end(&mut buf).await?;
+                }
+                // without a length the string grows with the input, account for it afterwards
+                None => {
+                    reader.read_to_end(&mut buf).await?;
+                    ctx.limits.check_len(buf.len())?;
+                    ctx.limits.alloc(buf.len())?;
+                }
+            }
+
+            String::from_utf8(buf).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e).into())
+        }
+    }
+}
+
+// Arc is only Sync with Send contents, Rc never is, so a future holding on to one would not be Send
+macro_rules! impl_ptr {
+    ($P:ident) => {
+        impl<T: MtAsyncSerialize + ?Sized + Send> MtAsyncSerialize for $P<T> {
+            async fn mt_serialize_async_with<C: MtCfg, W: AsyncWrite + Unpin + Send>(
+                &self,
+                writer: &mut W,
+                ctx: &mut SerializeCtx<'_>,
+            ) -> Result<(), SerializeError> {
+                // boxing the future allows recursive types
+                Box::pin(self.deref().mt_serialize_async_with::<C, _>(writer, ctx)).await
+            }
+        }
+    };
+}
+
+impl_ptr!(Box);
+impl_ptr!(Arc);
+
+impl<T> MtAsyncSerialize for Cow<'_, T>
+where
+    T: MtAsyncSerialize + ToOwned + ?Sized,
+    T::Owned: Sync,
+{
+    async fn mt_serialize_async_with<C: MtCfg, W: AsyncWrite + Unpin + Send>(
+        &self,
+        writer: &mut W,
+        ctx: &mut SerializeCtx<'_>,
+    ) -> Result<(), SerializeError> {
+        self.deref()
+            .mt_serialize_async_with::<C, _>(writer, ctx)
+            .await
+    }
+}
+
+impl<T: MtAsyncDeserialize> MtAsyncDeserialize for Box<T> {
+    async fn mt_deserialize_async_with<C: MtCfg, R: AsyncRead + Unpin + Send>(
+        reader: &mut R,
+        ctx: &mut DeserializeCtx<'_>,
+    ) -> Result<Self, DeserializeError> {
+        ctx.limits.alloc(core::mem::size_of::<T>())?;
+        Ok(Self::new(nested_async::<T, C, _>(reader, ctx).await?))
+    }
+}
+
+impl<T: MtAsyncSerialize> MtAsyncSerialize for RangeInclusive<T> {
+    async fn mt_serialize_async_with<C: MtCfg, W: AsyncWrite + Unpin + Send>(
+        &self,
+        writer: &mut W,
+        ctx: &mut SerializeCtx<'_>,
+    ) -> Result<(), SerializeError> {
+        self.start()
+            .mt_serialize_async_with::<DefCfg, _>(writer, ctx)
+            .await?;
+        self.end()
+            .mt_serialize_async_with::<DefCfg, _>(writer, ctx)
+            .await?;
+
+        Ok(())
+    }
+}
+
+impl<T: MtAsyncDeserialize> MtAsyncDeserialize for RangeInclusive<T> {
+    async fn mt_deserialize_async_with<C: MtCfg, R: AsyncRead + Unpin + Send>(
+        reader: &mut R,
+        ctx: &mut DeserializeCtx<'_>,
+    ) -> Result<Self, DeserializeError> {
+        let start = T::mt_deserialize_async_with::<DefCfg, _>(reader, ctx).await?;
+        let end = T::mt_deserialize_async_with::<DefCfg, _>(reader, ctx).await?;
+
+        Ok(start..=end)
+    }
+}
@@ -0,0 +1,37 @@
+use super::*;
+
+// the value of a #[mt(bits = N)] field. consecutive bit fields are packed MSB-first into a u8,
+// u16 or u32, to_bits has to fit into N bits (SerializeError::BitsOverflow otherwise) and
+// from_bits gets the N bits read back. implement this for enums like a facedir rotation
+pub trait MtBits: Sized {
+    fn to_bits(&self) -> u32;
+    fn from_bits(bits: u32) -> Result<Self, DeserializeError>;
+}
+
+impl MtBits for bool {
+    fn to_bits(&self) -> u32 {
+        *self as u32
+    }
+
+    fn from_bits(bits: u32) -> Result<Self, DeserializeError> {
+        Ok(bits != 0)
+    }
+}
+
+macro_rules! impl_bits {
+    ($($T:ty),*) => {
+        $(
+            impl MtBits for $T {
+                fn to_bits(&self) -> u32 {
+                    (*self).into()
+                }
+
+                fn from_bits(bits: u32) -> Result<Self, DeserializeError> {
+                    Ok(bits.try_into()?)
+                }
+            }
+        )*
+    };
+}
+
+impl_bits!(u8, u16, u32);
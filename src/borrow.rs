@@ -0,0 +1,315 @@
+use super::*;
+
+// like MtDeserialize, but reads from an in-memory buffer and may borrow from it
+// implementors provide at least one of the two methods, like with MtDeserialize
+pub trait MtDeserializeBorrowed<'a>: Sized {
+    fn mt_deserialize_borrowed<C: MtCfg>(reader: &mut &'a [u8]) -> Result<Self, DeserializeError> {
+        Self::mt_deserialize_borrowed_with::<C>(reader, &mut DeserializeCtx::default())
+    }
+
+    fn mt_deserialize_borrowed_with<C: MtCfg>(
+        reader: &mut &'a [u8],
+        ctx: &mut DeserializeCtx,
+    ) -> Result<Self, DeserializeError> {
+        let _ = ctx;
+        Self::mt_deserialize_borrowed::<C>(reader)
+    }
+}
+
+// splits off up to len bytes, like io::Read::take followed by draining the rest
+pub fn mt_take_borrowed<'a>(reader: &mut &'a [u8], len: usize) -> &'a [u8] {
+    let (taken, rest) = reader.split_at(len.min(reader.len()));
+    *reader = rest;
+    taken
+}
+
+// applies the same limits as mt_deserialize_sized_seq_with
+pub fn mt_deserialize_seq_borrowed<'a, C, T, B>(
+    reader: &mut &'a [u8],
+    ctx: &mut DeserializeCtx,
+) -> Result<B, DeserializeError>
+where
+    C: MtCfg,
+    T: MtDeserializeBorrowed<'a>,
+    B: FromIterator<T>,
+{
+    let len = C::read_len(reader)?;
+    let variable = len.option().is_none();
+
+    if let Some(x) = len.option() {
+        ctx.limits.check_len(x)?;
+    }
+
+    len.range()
+        .map_while(|i| {
+            let item = ctx
+                .limits
+                .check_len(i + 1)
+                .and_then(|_| ctx.limits.alloc(core::mem::size_of::<T>()))
+                .and_then(|_| {
+                    ctx.nested(|c| T::mt_deserialize_borrowed_with::<C::Inner>(reader, c))
+                });
+
+            seq_item(variable, i, item)
+        })
+        .collect()
+}
+
+macro_rules! impl_owned {
+    ($T:ty) => {
+        impl<'a> MtDeserializeBorrowed<'a> for $T {
+            fn mt_deserialize_borrowed_with<C: MtCfg>(
+                reader: &mut &'a [u8],
+                ctx: &mut DeserializeCtx,
+            ) -> Result<Self, DeserializeError> {
+                <$T as MtDeserialize>::mt_deserialize_with::<C>(reader, ctx)
+            }
+        }
+    };
+}
+
+impl_owned!(u8);
+impl_owned!(i8);
+impl_owned!(u16);
+impl_owned!(i16);
+impl_owned!(u32);
+impl_owned!(i32);
+impl_owned!(f32);
+impl_owned!(u64);
+impl_owned!(i64);
+impl_owned!(f64);
+impl_owned!(());
+impl_owned!(bool);
+impl_owned!(String);
+
+impl<'de: 'a, 'a> MtDeserializeBorrowed<'de> for &'a [u8] {
+    fn mt_deserialize_borrowed_with<C: MtCfg>(
+        reader: &mut &'de [u8],
+        ctx: &mut DeserializeCtx,
+    ) -> Result<Self, DeserializeError> {
+        let len = C::read_len(reader)?;
+
+        // nothing is allocated, but the length is still capped
+        let x = len.option().unwrap_or(reader.len());
+        ctx.limits.check_len(x)?;
+
+        if x > reader.len() {
+            return Err(DeserializeError::UnexpectedEof);
+        }
+
+        Ok(mt_take_borrowed(reader, x))
+    }
+}
+
+impl<'de: 'a, 'a> MtDeserializeBorrowed<'de> for &'a str {
+    fn mt_deserialize_borrowed_with<C: MtCfg>(
+        reader: &mut &'de [u8],
+        ctx: &mut DeserializeCtx,
+    ) -> Result<Self, DeserializeError> {
+        if C::utf16() {
+            return Err(DeserializeError::Other(
+                "UTF-16 strings can not be borrowed".into(),
+            ));
+        }
+
+        let bytes = <&[u8]>::mt_deserialize_borrowed_with::<C>(reader, ctx)?;
+
+        core::str::from_utf8(bytes).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "stream did not contain valid UTF-8",
+            )
+            .into()
+        })
+    }
+}
+
+impl<'de: 'a, 'a> MtDeserializeBorrowed<'de> for Cow<'a, str> {
+    fn mt_deserialize_borrowed_with<C: MtCfg>(
+        reader: &mut &'de [u8],
+        ctx: &mut DeserializeCtx,
+    ) -> Result<Self, DeserializeError> {
+        if C::utf16() {
+            Ok(Cow::Owned(String::mt_deserialize_with::<C>(reader, ctx)?))
+        } else {
+            Ok(Cow::Borrowed(<&str>::mt_deserialize_borrowed_with::<C>(
+                reader, ctx,
+            )?))
+        }
+    }
+}
+
+impl<'de: 'a, 'a> MtDeserializeBorrowed<'de> for Cow<'a, [u8]> {
+    fn mt_deserialize_borrowed_with<C: MtCfg>(
+        reader: &mut &'de [u8],
+        ctx: &mut DeserializeCtx,
+    ) -> Result<Self, DeserializeError> {
+        Ok(Cow::Borrowed(<&[u8]>::mt_deserialize_borrowed_with::<C>(
+            reader, ctx,
+        )?))
+    }
+}
+
+impl<'a, T: MtDeserializeBorrowed<'a>> MtDeserializeBorrowed<'a> for Option<T> {
+    fn mt_deserialize_borrowed_with<C: MtCfg>(
+        reader: &mut &'a [u8],
+        ctx: &mut DeserializeCtx,
+    ) -> Result<Self, DeserializeError> {
+        match T::mt_deserialize_borrowed_with::<C>(reader, ctx) {
+            Err(e) if matches!(e.inner(), DeserializeError::UnexpectedEof) => Ok(None),
+            x => x.map(Some),
+        }
+    }
+}
+
+impl<'a, T: MtDeserializeBorrowed<'a>> MtDeserializeBorrowed<'a> for Box<T> {
+    fn mt_deserialize_borrowed_with<C: MtCfg>(
+        reader: &mut &'a [u8],
+        ctx: &mut DeserializeCtx,
+    ) -> Result<Self, DeserializeError> {
+        ctx.limits.alloc(core::mem::size_of::<T>())?;
+        let item = ctx.nested(|c| T::mt_deserialize_borrowed_with::<C>(reader, c))?;
+
+        Ok(Self::new(item))
+    }
+}
+
+impl<'a, T: MtDeserializeBorrowed<'a>> MtDeserializeBorrowed<'a> for Vec<T> {
+    fn mt_deserialize_borrowed_with<C: MtCfg>(
+        reader: &mut &'a [u8],
+        ctx: &mut DeserializeCtx,
+    ) -> Result<Self, DeserializeError> {
+        mt_deserialize_seq_borrowed::<C, _, _>(reader, ctx)
+    }
+}
+
+impl<'a, T, const N: usize> MtDeserializeBorrowed<'a> for [T; N]
+where
+    T: MtDeserializeBorrowed<'a>,
+{
+    fn mt_deserialize_borrowed_with<C: MtCfg>(
+        reader: &mut &'a [u8],
+        ctx: &mut DeserializeCtx,
+    ) -> Result<Self, DeserializeError> {
+        try_array(|i| {
+            T::mt_deserialize_borrowed_with::<DefCfg>(reader, ctx).map_err(|e| e.at_index(i))
+        })
+    }
+}
+
+impl<'a, T, E> MtDeserializeBorrowed<'a> for EnumSet<E>
+where
+    T: MtDeserialize,
+    E: EnumSetTypeWithRepr<Repr = T>,
+{
+    fn mt_deserialize_borrowed_with<C: MtCfg>(
+        reader: &mut &'a [u8],
+        ctx: &mut DeserializeCtx,
+    ) -> Result<Self, DeserializeError> {
+        Self::mt_deserialize_with::<C>(reader, ctx)
+    }
+}
+
+impl<'a, T: MtDeserializeBorrowed<'a>> MtDeserializeBorrowed<'a> for VecDeque<T> {
+    fn mt_deserialize_borrowed_with<C: MtCfg>(
+        reader: &mut &'a [u8],
+        ctx: &mut DeserializeCtx,
+    ) -> Result<Self, DeserializeError> {
+        mt_deserialize_seq_borrowed::<C, _, _>(reader, ctx)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a, T, S> MtDeserializeBorrowed<'a> for HashSet<T, S>
+where
+    T: MtDeserializeBorrowed<'a> + Eq + Hash,
+    S: BuildHasher + Default,
+{
+    fn mt_deserialize_borrowed_with<C: MtCfg>(
+        reader: &mut &'a [u8],
+        ctx: &mut DeserializeCtx,
+    ) -> Result<Self, DeserializeError> {
+        mt_deserialize_seq_borrowed::<C, _, _>(reader, ctx)
+    }
+}
+
+impl<'a, T> MtDeserializeBorrowed<'a> for BTreeSet<T>
+where
+    T: MtDeserializeBorrowed<'a> + Ord,
+{
+    fn mt_deserialize_borrowed_with<C: MtCfg>(
+        reader: &mut &'a [u8],
+        ctx: &mut DeserializeCtx,
+    ) -> Result<Self, DeserializeError> {
+        mt_deserialize_seq_borrowed::<C, _, _>(reader, ctx)
+    }
+}
+
+#[cfg(feature = "indexmap")]
+impl<'a, T, S> MtDeserializeBorrowed<'a> for IndexSet<T, S>
+where
+    T: MtDeserializeBorrowed<'a> + Eq + Hash,
+    S: BuildHasher + Default,
+{
+    fn mt_deserialize_borrowed_with<C: MtCfg>(
+        reader: &mut &'a [u8],
+        ctx: &mut DeserializeCtx,
+    ) -> Result<Self, DeserializeError> {
+        mt_deserialize_seq_borrowed::<C, _, _>(reader, ctx)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a, K, V, S> MtDeserializeBorrowed<'a> for HashMap<K, V, S>
+where
+    K: MtDeserializeBorrowed<'a> + Eq + Hash,
+    V: MtDeserializeBorrowed<'a>,
+    S: BuildHasher + Default,
+{
+    fn mt_deserialize_borrowed_with<C: MtCfg>(
+        reader: &mut &'a [u8],
+        ctx: &mut DeserializeCtx,
+    ) -> Result<Self, DeserializeError> {
+        mt_deserialize_seq_borrowed::<C, _, _>(reader, ctx)
+    }
+}
+
+impl<'a, K, V> MtDeserializeBorrowed<'a> for BTreeMap<K, V>
+where
+    K: MtDeserializeBorrowed<'a> + Ord,
+    V: MtDeserializeBorrowed<'a>,
+{
+    fn mt_deserialize_borrowed_with<C: MtCfg>(
+        reader: &mut &'a [u8],
+        ctx: &mut DeserializeCtx,
+    ) -> Result<Self, DeserializeError> {
+        mt_deserialize_seq_borrowed::<C, _, _>(reader, ctx)
+    }
+}
+
+#[cfg(feature = "indexmap")]
+impl<'a, K, V, S> MtDeserializeBorrowed<'a> for IndexMap<K, V, S>
+where
+    K: MtDeserializeBorrowed<'a> + Eq + Hash,
+    V: MtDeserializeBorrowed<'a>,
+    S: BuildHasher + Default,
+{
+    fn mt_deserialize_borrowed_with<C: MtCfg>(
+        reader: &mut &'a [u8],
+        ctx: &mut DeserializeCtx,
+    ) -> Result<Self, DeserializeError> {
+        mt_deserialize_seq_borrowed::<C, _, _>(reader, ctx)
+    }
+}
+
+impl<'a, T: MtDeserializeBorrowed<'a>> MtDeserializeBorrowed<'a> for RangeInclusive<T> {
+    fn mt_deserialize_borrowed_with<C: MtCfg>(
+        reader: &mut &'a [u8],
+        ctx: &mut DeserializeCtx,
+    ) -> Result<Self, DeserializeError> {
+        let start = T::mt_deserialize_borrowed_with::<DefCfg>(reader, ctx)?;
+        let end = T::mt_deserialize_borrowed_with::<DefCfg>(reader, ctx)?;
+
+        Ok(start..=end)
+    }
+}
@@ -0,0 +1,130 @@
+use super::*;
+use crate as mt_ser;
+use core::{fmt, str::FromStr};
+
+// Minetest's video::SColor, encoded as its u32 0xAARRGGBB, so the bytes are in ARGB order
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    Default,
+    MtSerialize,
+    MtDeserialize,
+    MtDeserializeBorrowed,
+)]
+#[cfg_attr(feature = "async", derive(MtAsyncSerialize, MtAsyncDeserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "proptest", derive(MtStrategy))]
+#[cfg_attr(feature = "random", derive(GenerateRandom))]
+pub struct Color {
+    pub a: u8,
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Color {
+    pub const fn new(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Self { a, r, g, b }
+    }
+
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Self::new(r, g, b, 0xFF)
+    }
+}
+
+// RGBA, like the components of ColorSpec in Lua
+impl From<[u8; 4]> for Color {
+    fn from([r, g, b, a]: [u8; 4]) -> Self {
+        Self::new(r, g, b, a)
+    }
+}
+
+impl From<Color> for [u8; 4] {
+    fn from(c: Color) -> Self {
+        [c.r, c.g, c.b, c.a]
+    }
+}
+
+// ARGB, like SColor::color
+impl From<u32> for Color {
+    fn from(x: u32) -> Self {
+        let [a, r, g, b] = x.to_be_bytes();
+        Self { a, r, g, b }
+    }
+}
+
+impl From<Color> for u32 {
+    fn from(c: Color) -> Self {
+        u32::from_be_bytes([c.a, c.r, c.g, c.b])
+    }
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("invalid color: {0:?}")]
+pub struct ParseColorError(pub String);
+
+// the hex forms of parseColorString: #RGB, #RGBA, #RRGGBB and #RRGGBBAA, alpha defaults
+// to opaque. named colors are not supported
+impl FromStr for Color {
+    type Err = ParseColorError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || ParseColorError(s.into());
+
+        let hex = s.strip_prefix('#').ok_or_else(err)?;
+        if !hex.bytes().all(|c| c.is_ascii_hexdigit()) {
+            return Err(err());
+        }
+
+        let digit = |i: usize| u8::from_str_radix(&hex[i..i + 1], 16).unwrap();
+        let pair = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).unwrap();
+
+        match hex.len() {
+            3 | 4 => {
+                let mut c = [0xFF; 4];
+                for (i, x) in c.iter_mut().take(hex.len()).enumerate() {
+                    *x = digit(i) * 0x11;
+                }
+                Ok(c.into())
+            }
+            6 | 8 => {
+                let mut c = [0xFF; 4];
+                for (i, x) in c.iter_mut().take(hex.len() / 2).enumerate() {
+                    *x = pair(2 * i);
+                }
+                Ok(c.into())
+            }
+            _ => Err(err()),
+        }
+    }
+}
+
+// always #RRGGBBAA, which every version of Minetest parses
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "#{:02X}{:02X}{:02X}{:02X}",
+            self.r, self.g, self.b, self.a
+        )
+    }
+}
+
+// as the u32, so the serde wire format matches the mt_ser one
+#[cfg(feature = "serde")]
+impl ::serde::Serialize for Color {
+    fn serialize<S: ::serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u32((*self).into())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> ::serde::Deserialize<'de> for Color {
+    fn deserialize<D: ::serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        u32::deserialize(deserializer).map(Into::into)
+    }
+}
@@ -0,0 +1,72 @@
+use super::*;
+use core::any::{type_name, Any};
+
+// runtime options for a single serialization, threaded through MtSerialize::mt_serialize_with
+#[derive(Debug, Default)]
+pub struct SerializeCtx<'a> {
+    pub zlib_level: Option<u32>, // overrides #[mt(zlib(level = ..))] for every zlib field, 9 above that
+    pub zstd_level: Option<i32>, // overrides #[mt(zstd(level = ..))] for every zstd field
+    pub version: Option<u16>,    // for since/until in the derive, None is the newest
+    // leading #[mt(version)] of a struct or variant, by its name as in errors (Type or
+    // Type::Variant). this is not the protocol version above, without an entry the
+    // max_version is written
+    pub struct_versions: BTreeMap<&'static str, u64>,
+    pub user: Option<&'a mut (dyn Any + Send)>, // see user()
+}
+
+impl SerializeCtx<'_> {
+    // the caller's own state, for custom impls and map_ser with #[mt(context = "T")].
+    // it is looked up by type at runtime, so it has to be 'static (it can not hold
+    // borrows) and T has to be exactly the type that was put in. it also has to be Send
+    // so that async serialization can hold on to it across awaits
+    pub fn user<T: Any>(&mut self) -> Result<&mut T, SerializeError> {
+        match self.user.as_deref_mut() {
+            Some(x) => x
+                .downcast_mut()
+                .ok_or(SerializeError::WrongContextType(type_name::<T>())),
+            None => Err(SerializeError::MissingContext(type_name::<T>())),
+        }
+    }
+}
+
+// state of a single deserialization, threaded through MtDeserialize::mt_deserialize_with
+#[derive(Debug, Default)]
+pub struct DeserializeCtx<'a> {
+    pub limits: Limits,
+    pub version: Option<u16>, // for since/until in the derive, None is the newest
+    pub user: Option<&'a mut (dyn Any + Send)>, // see user()
+    pub trace: Option<Trace>, // filled in by derived code, see mt_deserialize_traced
+}
+
+impl DeserializeCtx<'_> {
+    // like SerializeCtx::user, for map_des
+    pub fn user<T: Any>(&mut self) -> Result<&mut T, DeserializeError> {
+        match self.user.as_deref_mut() {
+            Some(x) => x
+                .downcast_mut()
+                .ok_or(DeserializeError::WrongContextType(type_name::<T>())),
+            None => Err(DeserializeError::MissingContext(type_name::<T>())),
+        }
+    }
+
+    // runs f one level deeper, see Limits::nested
+    pub fn nested<T>(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> Result<T, DeserializeError>,
+    ) -> Result<T, DeserializeError> {
+        self.limits.enter()?;
+        let res = f(self);
+        self.limits.leave();
+
+        res
+    }
+}
+
+// whether something limited to since..until is present in version,
+// None is newer than every version and so only excludes what has an until
+pub fn mt_version_in(version: Option<u16>, since: Option<u16>, until: Option<u16>) -> bool {
+    match version {
+        Some(v) => since.is_none_or(|s| v >= s) && until.is_none_or(|u| v < u),
+        None => until.is_none(),
+    }
+}
@@ -0,0 +1,277 @@
+use super::*;
+use core::{cmp::Ordering, marker::PhantomData};
+
+// a float F encoded as the integer I of F * SCALE, e.g. Minetest's writeF1000. R picks how
+// the scaled value is rounded, O what happens to values outside of I (Reject with
+// SerializeError::OutOfRange, or Saturate). deserializing is exact up to the precision of F
+pub struct Fixed<F, I, const SCALE: u32, R = Round, O = Reject>(pub F, PhantomData<(I, R, O)>);
+
+pub type F1000 = Fixed<f32, i32, 1000>;
+
+#[cfg(feature = "cgmath")]
+pub type V3F1000 = Vector3<F1000>;
+
+// like the C++ cast, but 0.7f32 is slightly less than 0.7 and turns into 699
+pub struct Trunc;
+// keeps values that came from an integer as they are
+pub struct Round;
+
+pub struct Reject;
+pub struct Saturate;
+
+pub trait FixedRound {
+    fn round(x: f64) -> f64;
+}
+
+// without std there is no f64::trunc, casting is exact for anything with a fraction
+fn trunc(x: f64) -> f64 {
+    if x.abs() < (1u64 << 52) as f64 {
+        x as i64 as f64
+    } else {
+        x
+    }
+}
+
+impl FixedRound for Trunc {
+    fn round(x: f64) -> f64 {
+        trunc(x)
+    }
+}
+
+// half away from zero, like f64::round
+impl FixedRound for Round {
+    fn round(x: f64) -> f64 {
+        let t = trunc(x);
+        match x - t {
+            d if d >= 0.5 => t + 1.0,
+            d if d <= -0.5 => t - 1.0,
+            _ => t,
+        }
+    }
+}
+
+pub trait FixedOverflow {
+    const SATURATE: bool;
+}
+
+impl FixedOverflow for Reject {
+    const SATURATE: bool = false;
+}
+
+impl FixedOverflow for Saturate {
+    const SATURATE: bool = true;
+}
+
+pub trait FixedFloat: Copy {
+    fn to_f64(self) -> f64;
+    fn from_f64(x: f64) -> Self;
+}
+
+impl FixedFloat for f32 {
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+
+    fn from_f64(x: f64) -> Self {
+        x as f32
+    }
+}
+
+impl FixedFloat for f64 {
+    fn to_f64(self) -> f64 {
+        self
+    }
+
+    fn from_f64(x: f64) -> Self {
+        x
+    }
+}
+
+pub trait FixedInt: MtSerialize + MtDeserialize + Copy {
+    const MIN: f64;
+    const END: f64; // exclusive, MAX itself may round up as f64
+
+    fn to_f64(self) -> f64;
+    fn from_f64(x: f64) -> Self; // saturating, like as
+}
+
+macro_rules! impl_fixed_int {
+    ($($T:ty),*) => {
+        $(
+            impl FixedInt for $T {
+                const MIN: f64 = <$T>::MIN as f64;
+                const END: f64 = <$T>::MAX as f64 + 1.0;
+
+                fn to_f64(self) -> f64 {
+                    self as f64
+                }
+
+                fn from_f64(x: f64) -> Self {
+                    x as $T
+                }
+            }
+        )*
+    };
+}
+
+impl_fixed_int!(u8, i8, u16, i16, u32, i32, u64, i64);
+
+impl<F, I, const SCALE: u32, R, O> Fixed<F, I, SCALE, R, O> {
+    pub const fn new(value: F) -> Self {
+        Self(value, PhantomData)
+    }
+}
+
+impl<F: FixedFloat, I: FixedInt, const SCALE: u32, R: FixedRound, O: FixedOverflow>
+    Fixed<F, I, SCALE, R, O>
+{
+    pub fn to_int(&self) -> Result<I, SerializeError> {
+        let x = R::round(self.0.to_f64() * SCALE as f64);
+
+        if O::SATURATE {
+            // NaN becomes 0, like with as
+            Ok(I::from_f64(x))
+        } else if x >= I::MIN && x < I::END {
+            Ok(I::from_f64(x))
+        } else {
+            Err(SerializeError::OutOfRange(
+                self.0.to_f64(),
+                core::any::type_name::<Self>(),
+            ))
+        }
+    }
+}
+
+impl<F: FixedFloat, I: FixedInt, const SCALE: u32, R, O> Fixed<F, I, SCALE, R, O> {
+    pub fn from_int(x: I) -> Self {
+        Self::new(F::from_f64(x.to_f64() / SCALE as f64))
+    }
+}
+
+impl<F: FixedFloat, I: FixedInt, const SCALE: u32, R: FixedRound, O: FixedOverflow> MtSerialize
+    for Fixed<F, I, SCALE, R, O>
+{
+    fn mt_serialize<C: MtCfg>(&self, writer: &mut impl Write) -> Result<(), SerializeError> {
+        self.to_int()?.mt_serialize::<DefCfg>(writer)
+    }
+
+    fn mt_serialized_len_with<C: MtCfg>(
+        &self,
+        ctx: &mut SerializeCtx,
+    ) -> Result<Option<usize>, SerializeError> {
+        self.to_int()?.mt_serialized_len_with::<DefCfg>(ctx)
+    }
+}
+
+impl<F: FixedFloat, I: FixedInt, const SCALE: u32, R: FixedRound, O: FixedOverflow> MtDeserialize
+    for Fixed<F, I, SCALE, R, O>
+{
+    fn mt_deserialize<C: MtCfg>(reader: &mut impl Read) -> Result<Self, DeserializeError> {
+        Ok(Self::from_int(I::mt_deserialize::<DefCfg>(reader)?))
+    }
+}
+
+impl<'a, F: FixedFloat, I: FixedInt, const SCALE: u32, R: FixedRound, O: FixedOverflow>
+    MtDeserializeBorrowed<'a> for Fixed<F, I, SCALE, R, O>
+{
+    fn mt_deserialize_borrowed<C: MtCfg>(reader: &mut &'a [u8]) -> Result<Self, DeserializeError> {
+        Self::mt_deserialize::<C>(reader)
+    }
+}
+
+#[cfg(feature = "async")]
+impl<F, I, const SCALE: u32, R, O> MtAsyncSerialize for Fixed<F, I, SCALE, R, O>
+where
+    F: FixedFloat + Sync,
+    I: FixedInt + MtAsyncSerialize + Send,
+    R: FixedRound + Sync,
+    O: FixedOverflow + Sync,
+{
+    async fn mt_serialize_async<C: MtCfg, W: tokio::io::AsyncWrite + Unpin + Send>(
+        &self,
+        writer: &mut W,
+    ) -> Result<(), SerializeError> {
+        self.to_int()?.mt_serialize_async::<DefCfg, _>(writer).await
+    }
+}
+
+#[cfg(feature = "async")]
+impl<F, I, const SCALE: u32, R, O> MtAsyncDeserialize for Fixed<F, I, SCALE, R, O>
+where
+    F: FixedFloat + Send,
+    I: FixedInt + MtAsyncDeserialize,
+    R: FixedRound + Send,
+    O: FixedOverflow + Send,
+{
+    async fn mt_deserialize_async<C: MtCfg, Rd: tokio::io::AsyncRead + Unpin + Send>(
+        reader: &mut Rd,
+    ) -> Result<Self, DeserializeError> {
+        Ok(Self::from_int(
+            I::mt_deserialize_async::<DefCfg, _>(reader).await?,
+        ))
+    }
+}
+
+// only the value counts, the marker types need no impls of their own
+impl<F: Clone, I, const SCALE: u32, R, O> Clone for Fixed<F, I, SCALE, R, O> {
+    fn clone(&self) -> Self {
+        Self::new(self.0.clone())
+    }
+}
+
+impl<F: Copy, I, const SCALE: u32, R, O> Copy for Fixed<F, I, SCALE, R, O> {}
+
+impl<F: Default, I, const SCALE: u32, R, O> Default for Fixed<F, I, SCALE, R, O> {
+    fn default() -> Self {
+        Self::new(F::default())
+    }
+}
+
+impl<F: Debug, I, const SCALE: u32, R, O> Debug for Fixed<F, I, SCALE, R, O> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl<F: PartialEq, I, const SCALE: u32, R, O> PartialEq for Fixed<F, I, SCALE, R, O> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl<F: PartialOrd, I, const SCALE: u32, R, O> PartialOrd for Fixed<F, I, SCALE, R, O> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.0.partial_cmp(&other.0)
+    }
+}
+
+impl<F, I, const SCALE: u32, R, O> From<F> for Fixed<F, I, SCALE, R, O> {
+    fn from(value: F) -> Self {
+        Self::new(value)
+    }
+}
+
+// values made from an integer are in range and survive the roundtrip
+#[cfg(feature = "proptest")]
+impl<F, I, const SCALE: u32, R, O> MtStrategy for Fixed<F, I, SCALE, R, O>
+where
+    F: FixedFloat + Debug + 'static,
+    I: FixedInt + MtStrategy,
+    R: 'static,
+    O: 'static,
+{
+    fn mt_strategy<C: MtCfg>() -> proptest::strategy::BoxedStrategy<Self> {
+        use proptest::strategy::Strategy;
+
+        I::mt_strategy::<DefCfg>().prop_map(Self::from_int).boxed()
+    }
+}
+
+#[cfg(feature = "random")]
+impl<F: FixedFloat, I: FixedInt + GenerateRandom, const SCALE: u32, R, O> GenerateRandom
+    for Fixed<F, I, SCALE, R, O>
+{
+    fn generate_random<C: MtCfg>(rng: &mut impl rand::Rng) -> Self {
+        Self::from_int(I::generate_random::<DefCfg>(rng))
+    }
+}
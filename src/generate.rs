@@ -0,0 +1,42 @@
+use super::*;
+
+// the limits MtStrategy and GenerateRandom share, so that both produce the same kind of values
+
+// longer sequences only slow tests down and make for larger values,
+// but u8 length prefixes still run into their own limit
+const MAX_SEQ: usize = 300;
+
+pub(crate) fn max_seq_len<C: MtCfg>() -> usize {
+    C::max_len().min(MAX_SEQ)
+}
+
+// as many chars as fit into the length prefix, counted in bytes or UTF-16 units
+pub(crate) fn fit_string<C: MtCfg>(chars: impl IntoIterator<Item = char>) -> String {
+    let max = C::max_len();
+    let mut st = String::new();
+    let mut len = 0;
+
+    for c in chars {
+        len += if C::utf16() {
+            c.len_utf16()
+        } else {
+            c.len_utf8()
+        };
+        if len > max {
+            break;
+        }
+        st.push(c);
+    }
+
+    st
+}
+
+// the largest value of a #[mt(bits = N)] field
+pub(crate) fn max_bits(bits: u8) -> u32 {
+    u32::MAX
+        .checked_shr(32 - u32::from(bits.min(32)))
+        .unwrap_or(0)
+}
+
+// entries are pairs, so the key gets C::Inner and the value C::Inner::Inner
+pub(crate) type MapEntry<K, V> = (K, V);
@@ -1,41 +1,233 @@
-#![feature(array_try_from_fn)]
-#![feature(iterator_try_collect)]
+#![cfg_attr(not(feature = "std"), no_std)]
 
+extern crate alloc;
+
+#[cfg(feature = "arbitrary")]
+pub use arbitrary;
+#[cfg(feature = "zlib")]
 pub use flate2;
-pub use mt_ser_derive::{mt_derive, MtDeserialize, MtSerialize};
+pub use mt_ser_derive::{mt_derive, MtDeserialize, MtDeserializeBorrowed, MtSerialize};
 pub use paste;
+#[cfg(feature = "zstd")]
 pub use zstd;
 
-use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+#[cfg(feature = "async")]
+pub use async_compression;
+#[cfg(feature = "async")]
+pub use mt_ser_derive::{MtAsyncDeserialize, MtAsyncSerialize};
+#[cfg(feature = "async")]
+pub use tokio;
+
+#[cfg(feature = "proptest")]
+pub use mt_ser_derive::MtStrategy;
+#[cfg(feature = "proptest")]
+pub use proptest;
+
+#[cfg(feature = "random")]
+pub use mt_ser_derive::GenerateRandom;
+#[cfg(feature = "random")]
+pub use rand;
+#[cfg(feature = "random")]
+pub use rand_chacha;
+
+use alloc::{
+    borrow::{Cow, ToOwned},
+    boxed::Box,
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    rc::Rc,
+    string::String,
+    sync::Arc,
+    vec,
+    vec::Vec,
+};
+#[cfg(feature = "cgmath")]
 use cgmath::{Deg, Euler, Point1, Point2, Point3, Rad, Vector1, Vector2, Vector3, Vector4};
+#[cfg(feature = "collision")]
 use collision::{Aabb2, Aabb3};
-use enumset::{EnumSet, EnumSetTypeWithRepr};
-use paste::paste as paste_macro;
-use std::{
-    collections::{HashMap, HashSet},
+#[cfg(any(feature = "std", feature = "indexmap"))]
+use core::hash::{BuildHasher, Hash};
+use core::{
     convert::Infallible,
     fmt::Debug,
-    io::{self, Read, Write},
     num::TryFromIntError,
     ops::{Deref, Range, RangeFrom, RangeFull, RangeInclusive, RangeTo, RangeToInclusive},
 };
+use enumset::{EnumSet, EnumSetTypeWithRepr};
+#[cfg(feature = "indexmap")]
+use indexmap::{IndexMap, IndexSet};
+use io::{Read, Write};
+#[cfg(feature = "std")]
+use std::collections::{HashMap, HashSet};
 use thiserror::Error;
 
-#[cfg(test)]
+pub mod io;
+
+// used by the derive output, which can not rely on the std prelude
+#[doc(hidden)]
+pub mod __private {
+    pub use super::trace::{TraceDebug, TraceNoDebug, TraceValue};
+    pub use alloc::{boxed::Box, string::String, vec::Vec};
+}
+
+#[cfg(all(test, feature = "std"))]
 mod tests;
 
+mod borrow;
+pub use borrow::*;
+
+mod limits;
+pub use limits::*;
+
+mod ctx;
+pub use ctx::*;
+
+mod fixed;
+pub use fixed::*;
+
+mod bits;
+pub use bits::*;
+
+#[cfg(feature = "color")]
+mod color;
+#[cfg(feature = "color")]
+pub use color::*;
+
+mod trace;
+pub use trace::{mt_deserialize_traced, mt_deserialize_traced_with, Trace, TraceEntry};
+
+#[cfg(feature = "async")]
+mod async_io;
+#[cfg(feature = "async")]
+pub use async_io::*;
+
+#[cfg(any(feature = "proptest", feature = "random"))]
+mod generate;
+
+#[cfg(feature = "proptest")]
+mod strategy;
+#[cfg(feature = "proptest")]
+pub use strategy::*;
+
+#[cfg(feature = "random")]
+mod random;
+#[cfg(feature = "random")]
+pub use random::*;
+
+#[cfg(feature = "serde")]
+pub mod serde;
+
 use crate as mt_ser;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathSegment {
+    Field(&'static str),
+    Index(usize),
+}
+
+// location of a failing field, e.g. ToCltHudAdd.def.style[3].len
+// segments are pushed innermost first while the error unwinds
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FieldPath {
+    pub root: Option<&'static str>,
+    pub segments: Vec<PathSegment>,
+}
+
+impl FieldPath {
+    pub fn iter(&self) -> impl Iterator<Item = &PathSegment> {
+        self.segments.iter().rev()
+    }
+}
+
+impl core::fmt::Display for FieldPath {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        if let Some(root) = self.root {
+            write!(f, "{root}")?;
+        }
+
+        for seg in self.iter() {
+            match seg {
+                PathSegment::Field(name) => write!(f, ".{name}")?,
+                PathSegment::Index(i) => write!(f, "[{i}]")?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+macro_rules! impl_field_path {
+    ($E:ident $(, $wrap:ident)*) => {
+        impl $E {
+            pub fn in_field(self, ty: &'static str, field: &'static str) -> Self {
+                self.push_segment(PathSegment::Field(field), Some(ty))
+            }
+
+            pub fn at_index(self, index: usize) -> Self {
+                self.push_segment(PathSegment::Index(index), None)
+            }
+
+            fn push_segment(self, seg: PathSegment, root: Option<&'static str>) -> Self {
+                match self {
+                    $E::Field { mut path, source } => {
+                        path.segments.push(seg);
+                        path.root = root;
+                        $E::Field { path, source }
+                    }
+                    err => $E::Field {
+                        path: FieldPath {
+                            root,
+                            segments: vec![seg],
+                        },
+                        source: Box::new(err),
+                    },
+                }
+            }
+
+            pub fn path(&self) -> Option<&FieldPath> {
+                match self {
+                    $E::Field { path, .. } => Some(path),
+                    $($E::$wrap { source, .. } => source.path(),)*
+                    _ => None,
+                }
+            }
+
+            pub fn inner(&self) -> &Self {
+                match self {
+                    $E::Field { source, .. } $(| $E::$wrap { source, .. })* => source.inner(),
+                    err => err,
+                }
+            }
+        }
+    };
+}
+
 #[derive(Error, Debug)]
 pub enum SerializeError {
     #[error("io error: {0}")]
     IoError(#[from] io::Error),
     #[error("collection too big: {0}")]
     TooBig(#[from] TryFromIntError),
+    #[error("missing {0} context")]
+    MissingContext(&'static str),
+    #[error("context is not a {0}")]
+    WrongContextType(&'static str),
+    #[error("{0} out of range for {1}")]
+    OutOfRange(f64, &'static str),
+    #[error("{0} does not fit into {1} bits")]
+    BitsOverflow(u32, u8),
+    #[error("unsupported {0} version {1}")]
+    UnsupportedVersion(&'static str, u64),
     #[error("{0}")]
     Other(String),
+    #[error("{path}: {source}")]
+    Field {
+        path: FieldPath,
+        source: Box<SerializeError>,
+    },
 }
 
+impl_field_path!(SerializeError);
+
 impl From<Infallible> for SerializeError {
     fn from(_err: Infallible) -> Self {
         unreachable!("infallible")
@@ -51,13 +243,87 @@ pub enum DeserializeError {
     #[error("collection too big: {0}")]
     TooBig(#[from] TryFromIntError),
     #[error("invalid UTF-16: {0}")]
-    InvalidUtf16(#[from] std::char::DecodeUtf16Error),
+    InvalidUtf16(#[from] core::char::DecodeUtf16Error),
     #[error("invalid {0} enum variant {1:?}")]
     InvalidEnum(&'static str, Box<dyn Debug + Send + Sync>),
     #[error("invalid constant - wanted: {0:?} - got: {1:?}")]
     InvalidConst(Box<dyn Debug + Send + Sync>, Box<dyn Debug + Send + Sync>),
     #[error("{0}")]
+    LimitExceeded(Limit),
+    #[error("unsupported {0} version {1}")]
+    UnsupportedVersion(&'static str, u64),
+    #[error("missing {0} context")]
+    MissingContext(&'static str),
+    #[error("context is not a {0}")]
+    WrongContextType(&'static str),
+    #[error("{0}")]
     Other(String),
+    #[error("{path}: {source}")]
+    Field {
+        path: FieldPath,
+        source: Box<DeserializeError>,
+    },
+    #[error("at byte {offset}: {source}")]
+    Offset {
+        offset: u64,
+        source: Box<DeserializeError>,
+    },
+    #[error("at byte {offset} of the decompressed {codec} section: {source}")]
+    Section {
+        codec: &'static str,
+        offset: u64,
+        source: Box<DeserializeError>,
+    },
+}
+
+impl_field_path!(DeserializeError, Offset, Section);
+
+impl DeserializeError {
+    pub fn at_offset(self, offset: u64) -> Self {
+        match self {
+            // keep the innermost offset
+            err @ DeserializeError::Offset { .. } => err,
+            err => DeserializeError::Offset {
+                offset,
+                source: Box::new(err),
+            },
+        }
+    }
+
+    pub fn offset(&self) -> Option<u64> {
+        match self {
+            DeserializeError::Offset { offset, .. } => Some(*offset),
+            DeserializeError::Field { source, .. } => source.offset(),
+            _ => None,
+        }
+    }
+
+    // used by derived code, the offset is relative to the decompressed data
+    pub fn in_section(self, codec: &'static str, offset: u64) -> Self {
+        match self {
+            DeserializeError::Field { path, source } => DeserializeError::Field {
+                path,
+                source: Box::new(source.in_section(codec, offset)),
+            },
+            // keep the innermost section
+            err @ DeserializeError::Section { .. } => err,
+            err => DeserializeError::Section {
+                codec,
+                offset,
+                source: Box::new(err),
+            },
+        }
+    }
+
+    pub fn section_offset(&self) -> Option<(&'static str, u64)> {
+        match self {
+            DeserializeError::Section { codec, offset, .. } => Some((codec, *offset)),
+            DeserializeError::Field { source, .. } | DeserializeError::Offset { source, .. } => {
+                source.section_offset()
+            }
+            _ => None,
+        }
+    }
 }
 
 impl From<Infallible> for DeserializeError {
@@ -68,6 +334,11 @@ impl From<Infallible> for DeserializeError {
 
 impl From<io::Error> for DeserializeError {
     fn from(err: io::Error) -> Self {
+        #[cfg(feature = "std")]
+        if let Some(limit) = err.get_ref().and_then(|e| e.downcast_ref::<Limit>()) {
+            return DeserializeError::LimitExceeded(*limit);
+        }
+
         if err.kind() == io::ErrorKind::UnexpectedEof {
             DeserializeError::UnexpectedEof
         } else {
@@ -76,6 +347,14 @@ impl From<io::Error> for DeserializeError {
     }
 }
 
+// consumes and discards whatever is left in reader
+pub fn mt_drain(reader: &mut impl Read) -> Result<(), DeserializeError> {
+    let mut buf = [0; 256];
+    while reader.read(&mut buf)? != 0 {}
+
+    Ok(())
+}
+
 pub trait OrDefault<T> {
     fn or_default(self) -> Self;
 }
@@ -86,7 +365,8 @@ impl<'a, R: Read> Read for WrapRead<'a, R> {
         self.0.read(buf)
     }
 
-    fn read_vectored(&mut self, bufs: &mut [io::IoSliceMut<'_>]) -> io::Result<usize> {
+    #[cfg(feature = "std")]
+    fn read_vectored(&mut self, bufs: &mut [std::io::IoSliceMut<'_>]) -> io::Result<usize> {
         self.0.read_vectored(bufs)
     }
 
@@ -123,10 +403,95 @@ impl<'a, R: Read> Read for WrapRead<'a, R> {
     */
 }
 
+// counts the bytes consumed from the underlying reader, including those read
+// through the WrapRead/Take/decoder adapters used by zlib, zstd and size fields
+// (for compressed sections this is the amount of compressed input consumed,
+// errors from inside them also carry the offset into the decompressed data)
+pub struct CountRead<R: Read> {
+    inner: R,
+    pos: u64,
+}
+
+impl<R: Read> CountRead<R> {
+    pub fn new(inner: R) -> Self {
+        Self { inner, pos: 0 }
+    }
+
+    pub fn pos(&self) -> u64 {
+        self.pos
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read> Read for CountRead<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+
+    #[cfg(feature = "std")]
+    fn read_vectored(&mut self, bufs: &mut [std::io::IoSliceMut<'_>]) -> io::Result<usize> {
+        let n = self.inner.read_vectored(bufs)?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+// discards everything written to it, only keeping track of the length
+struct LenWrite(usize);
+
+impl Write for LenWrite {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0 += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// number of bytes C::write_len writes, which also rejects lengths C can not encode
+fn len_prefix<C: MtCfg>(len: usize) -> Result<usize, SerializeError> {
+    let mut writer = LenWrite(0);
+    C::write_len(len, &mut writer)?;
+    Ok(writer.0)
+}
+
+// used by the derived mt_serialized_len_with, len becomes None once a part is unknown
+#[doc(hidden)]
+pub fn mt_add_len(
+    len: &mut Option<usize>,
+    part: Result<Option<usize>, SerializeError>,
+) -> Result<(), SerializeError> {
+    let part = part?;
+    *len = len.zip(part).map(|(a, b)| a + b);
+    Ok(())
+}
+
+// same for a size field, whose bytes are written as a sequence with the C length
+#[doc(hidden)]
+pub fn mt_add_sized_len<C: MtCfg>(
+    len: &mut Option<usize>,
+    inner: Option<usize>,
+) -> Result<(), SerializeError> {
+    match inner {
+        Some(x) => mt_add_len(len, len_prefix::<C>(x).map(|p| Some(p + x))),
+        None => {
+            *len = None;
+            Ok(())
+        }
+    }
+}
+
 impl<T: MtDeserialize + Default> OrDefault<T> for Result<T, DeserializeError> {
     fn or_default(self) -> Self {
         match self {
-            Err(DeserializeError::UnexpectedEof) => Ok(T::default()),
+            Err(e) if matches!(e.inner(), DeserializeError::UnexpectedEof) => Ok(T::default()),
             x => x,
         }
     }
@@ -150,16 +515,91 @@ pub trait MtCfg {
         false
     }
 
+    // longest length write_len accepts (in UTF-16 units with utf16)
+    fn max_len() -> usize {
+        usize::MAX
+    }
+
     fn write_len(len: usize, writer: &mut impl Write) -> Result<(), SerializeError>;
     fn read_len(reader: &mut impl Read) -> Result<Self::Len, DeserializeError>;
+
+    #[cfg(feature = "async")]
+    fn read_len_async<R: tokio::io::AsyncRead + Unpin + Send>(
+        reader: &mut R,
+    ) -> impl core::future::Future<Output = Result<Option<usize>, DeserializeError>> + Send;
 }
 
+// implementors provide at least one of the two methods, the built-in impls and
+// the derive implement mt_serialize_with and pass the context on
 pub trait MtSerialize {
-    fn mt_serialize<C: MtCfg>(&self, writer: &mut impl Write) -> Result<(), SerializeError>;
+    fn mt_serialize<C: MtCfg>(&self, writer: &mut impl Write) -> Result<(), SerializeError> {
+        self.mt_serialize_with::<C>(writer, &mut SerializeCtx::default())
+    }
+
+    fn mt_serialize_with<C: MtCfg>(
+        &self,
+        writer: &mut impl Write,
+        ctx: &mut SerializeCtx,
+    ) -> Result<(), SerializeError> {
+        let _ = ctx;
+        self.mt_serialize::<C>(writer)
+    }
+
+    // number of bytes mt_serialize would produce, None if that depends on zlib/zstd output
+    fn mt_serialized_len<C: MtCfg>(&self) -> Result<Option<usize>, SerializeError> {
+        self.mt_serialized_len_with::<C>(&mut SerializeCtx::default())
+    }
+
+    // same for mt_serialize_with, for output that depends on the version or user context.
+    // the built-in impls and the derive add up the parts without encoding anything, this
+    // fallback for custom impls serializes the value and only counts the bytes
+    fn mt_serialized_len_with<C: MtCfg>(
+        &self,
+        ctx: &mut SerializeCtx,
+    ) -> Result<Option<usize>, SerializeError> {
+        let mut writer = LenWrite(0);
+        self.mt_serialize_with::<C>(&mut writer, ctx)?;
+        Ok(Some(writer.0))
+    }
 }
 
+// implementors provide at least one of the two methods, the built-in impls and
+// the derive implement mt_deserialize_with and pass the context on
 pub trait MtDeserialize: Sized {
-    fn mt_deserialize<C: MtCfg>(reader: &mut impl Read) -> Result<Self, DeserializeError>;
+    fn mt_deserialize<C: MtCfg>(reader: &mut impl Read) -> Result<Self, DeserializeError> {
+        Self::mt_deserialize_with::<C>(reader, &mut DeserializeCtx::default())
+    }
+
+    fn mt_deserialize_with<C: MtCfg>(
+        reader: &mut impl Read,
+        ctx: &mut DeserializeCtx,
+    ) -> Result<Self, DeserializeError> {
+        let _ = ctx;
+        Self::mt_deserialize::<C>(reader)
+    }
+
+    // shorthand for mt_deserialize_with with only limits set, limits is updated afterwards
+    fn mt_deserialize_limited<C: MtCfg>(
+        reader: &mut impl Read,
+        limits: &mut Limits,
+    ) -> Result<Self, DeserializeError> {
+        let mut ctx = DeserializeCtx {
+            limits: core::mem::take(limits),
+            ..Default::default()
+        };
+        let res = Self::mt_deserialize_with::<C>(reader, &mut ctx);
+        *limits = ctx.limits;
+
+        res
+    }
+}
+
+// like T::mt_deserialize::<C>, but errors carry the offset into reader at which decoding failed
+pub fn mt_deserialize_counted<C: MtCfg, T: MtDeserialize>(
+    reader: &mut impl Read,
+) -> Result<T, DeserializeError> {
+    let mut reader = CountRead::new(reader);
+    T::mt_deserialize::<C>(&mut reader).map_err(|e| e.at_offset(reader.pos()))
 }
 
 impl MtLen for usize {
@@ -167,7 +607,7 @@ impl MtLen for usize {
         Some(*self)
     }
 
-    type Range = std::ops::Range<usize>;
+    type Range = Range<usize>;
     fn range(&self) -> Self::Range {
         0..*self
     }
@@ -188,6 +628,13 @@ where
     type Len = usize;
     type Inner = DefCfg;
 
+    fn max_len() -> usize {
+        match core::mem::size_of::<Self>() {
+            n if n >= core::mem::size_of::<usize>() => usize::MAX,
+            n => (1 << (8 * n)) - 1,
+        }
+    }
+
     fn write_len(len: usize, writer: &mut impl Write) -> Result<(), SerializeError> {
         Self::try_from(len)?.mt_serialize::<DefCfg>(writer)
     }
@@ -195,6 +642,17 @@ where
     fn read_len(reader: &mut impl Read) -> Result<Self::Len, DeserializeError> {
         Ok(Self::mt_deserialize::<DefCfg>(reader)?.try_into()?)
     }
+
+    #[cfg(feature = "async")]
+    async fn read_len_async<R: tokio::io::AsyncRead + Unpin + Send>(
+        reader: &mut R,
+    ) -> Result<Option<usize>, DeserializeError> {
+        let mut buf = [0; 8];
+        let buf = &mut buf[..core::mem::size_of::<Self>()];
+        tokio::io::AsyncReadExt::read_exact(reader, buf).await?;
+
+        Ok(Some(Self::read_len(&mut &buf[..])?))
+    }
 }
 
 impl MtCfgLen for u8 {}
@@ -215,6 +673,13 @@ impl MtCfg for () {
     fn read_len(_writer: &mut impl Read) -> Result<Self::Len, DeserializeError> {
         Ok(())
     }
+
+    #[cfg(feature = "async")]
+    async fn read_len_async<R: tokio::io::AsyncRead + Unpin + Send>(
+        _reader: &mut R,
+    ) -> Result<Option<usize>, DeserializeError> {
+        Ok(None)
+    }
 }
 
 impl MtLen for () {
@@ -222,7 +687,7 @@ impl MtLen for () {
         None
     }
 
-    type Range = std::ops::RangeFrom<usize>;
+    type Range = RangeFrom<usize>;
     fn range(&self) -> Self::Range {
         0..
     }
@@ -243,6 +708,10 @@ impl<B: MtCfg> MtCfg for Utf16<B> {
         true
     }
 
+    fn max_len() -> usize {
+        B::max_len()
+    }
+
     fn write_len(len: usize, writer: &mut impl Write) -> Result<(), SerializeError> {
         B::write_len(len, writer)
     }
@@ -250,12 +719,23 @@ impl<B: MtCfg> MtCfg for Utf16<B> {
     fn read_len(reader: &mut impl Read) -> Result<Self::Len, DeserializeError> {
         B::read_len(reader)
     }
+
+    #[cfg(feature = "async")]
+    async fn read_len_async<R: tokio::io::AsyncRead + Unpin + Send>(
+        reader: &mut R,
+    ) -> Result<Option<usize>, DeserializeError> {
+        B::read_len_async(reader).await
+    }
 }
 
 impl<A: MtCfg, B: MtCfg> MtCfg for (A, B) {
     type Len = A::Len;
     type Inner = B;
 
+    fn max_len() -> usize {
+        A::max_len()
+    }
+
     fn write_len(len: usize, writer: &mut impl Write) -> Result<(), SerializeError> {
         A::write_len(len, writer)
     }
@@ -263,31 +743,12 @@ impl<A: MtCfg, B: MtCfg> MtCfg for (A, B) {
     fn read_len(reader: &mut impl Read) -> Result<Self::Len, DeserializeError> {
         A::read_len(reader)
     }
-}
-
-impl MtSerialize for u8 {
-    fn mt_serialize<C: MtCfg>(&self, writer: &mut impl Write) -> Result<(), SerializeError> {
-        writer.write_u8(*self)?;
-        Ok(())
-    }
-}
-
-impl MtDeserialize for u8 {
-    fn mt_deserialize<C: MtCfg>(reader: &mut impl Read) -> Result<Self, DeserializeError> {
-        Ok(reader.read_u8()?)
-    }
-}
 
-impl MtSerialize for i8 {
-    fn mt_serialize<C: MtCfg>(&self, writer: &mut impl Write) -> Result<(), SerializeError> {
-        writer.write_i8(*self)?;
-        Ok(())
-    }
-}
-
-impl MtDeserialize for i8 {
-    fn mt_deserialize<C: MtCfg>(reader: &mut impl Read) -> Result<Self, DeserializeError> {
-        Ok(reader.read_i8()?)
+    #[cfg(feature = "async")]
+    async fn read_len_async<R: tokio::io::AsyncRead + Unpin + Send>(
+        reader: &mut R,
+    ) -> Result<Option<usize>, DeserializeError> {
+        A::read_len_async(reader).await
     }
 }
 
@@ -298,23 +759,31 @@ macro_rules! impl_num {
                 &self,
                 writer: &mut impl Write,
             ) -> Result<(), SerializeError> {
-                paste_macro! {
-                    writer.[<write_ $T>]::<BigEndian>(*self)?;
-                }
+                writer.write_all(&self.to_be_bytes())?;
                 Ok(())
             }
+
+            fn mt_serialized_len_with<C: MtCfg>(
+                &self,
+                _ctx: &mut SerializeCtx,
+            ) -> Result<Option<usize>, SerializeError> {
+                Ok(Some(core::mem::size_of::<$T>()))
+            }
         }
 
         impl MtDeserialize for $T {
             fn mt_deserialize<C: MtCfg>(reader: &mut impl Read) -> Result<Self, DeserializeError> {
-                paste_macro! {
-                    Ok(reader.[<read_ $T>]::<BigEndian>()?)
-                }
+                let mut buf = [0; core::mem::size_of::<$T>()];
+                reader.read_exact(&mut buf)?;
+                Ok(Self::from_be_bytes(buf))
             }
         }
     };
 }
 
+impl_num!(u8);
+impl_num!(i8);
+
 impl_num!(u16);
 impl_num!(i16);
 
@@ -330,6 +799,13 @@ impl MtSerialize for () {
     fn mt_serialize<C: MtCfg>(&self, _writer: &mut impl Write) -> Result<(), SerializeError> {
         Ok(())
     }
+
+    fn mt_serialized_len_with<C: MtCfg>(
+        &self,
+        _ctx: &mut SerializeCtx,
+    ) -> Result<Option<usize>, SerializeError> {
+        Ok(Some(0))
+    }
 }
 
 impl MtDeserialize for () {
@@ -342,6 +818,13 @@ impl MtSerialize for bool {
     fn mt_serialize<C: MtCfg>(&self, writer: &mut impl Write) -> Result<(), SerializeError> {
         (*self as u8).mt_serialize::<DefCfg>(writer)
     }
+
+    fn mt_serialized_len_with<C: MtCfg>(
+        &self,
+        _ctx: &mut SerializeCtx,
+    ) -> Result<Option<usize>, SerializeError> {
+        Ok(Some(1))
+    }
 }
 
 impl MtDeserialize for bool {
@@ -350,9 +833,20 @@ impl MtDeserialize for bool {
     }
 }
 
-impl<T: MtSerialize> MtSerialize for &T {
-    fn mt_serialize<C: MtCfg>(&self, writer: &mut impl Write) -> Result<(), SerializeError> {
-        (*self).mt_serialize::<C>(writer)
+impl<T: MtSerialize + ?Sized> MtSerialize for &T {
+    fn mt_serialize_with<C: MtCfg>(
+        &self,
+        writer: &mut impl Write,
+        ctx: &mut SerializeCtx,
+    ) -> Result<(), SerializeError> {
+        (*self).mt_serialize_with::<C>(writer, ctx)
+    }
+
+    fn mt_serialized_len_with<C: MtCfg>(
+        &self,
+        ctx: &mut SerializeCtx,
+    ) -> Result<Option<usize>, SerializeError> {
+        (*self).mt_serialized_len_with::<C>(ctx)
     }
 }
 
@@ -362,8 +856,38 @@ pub fn mt_serialize_seq<C: MtCfg, T: MtSerialize>(
 ) -> Result<(), SerializeError> {
     C::write_len(iter.len(), writer)?;
 
-    iter.into_iter()
-        .try_for_each(|item| item.mt_serialize::<C::Inner>(writer))
+    iter.into_iter().enumerate().try_for_each(|(i, item)| {
+        item.mt_serialize::<C::Inner>(writer)
+            .map_err(|e| e.at_index(i))
+    })
+}
+
+pub fn mt_serialize_seq_with<C: MtCfg, T: MtSerialize>(
+    writer: &mut impl Write,
+    iter: impl ExactSizeIterator + IntoIterator<Item = T>,
+    ctx: &mut SerializeCtx,
+) -> Result<(), SerializeError> {
+    C::write_len(iter.len(), writer)?;
+
+    iter.into_iter().enumerate().try_for_each(|(i, item)| {
+        item.mt_serialize_with::<C::Inner>(writer, ctx)
+            .map_err(|e| e.at_index(i))
+    })
+}
+
+// the length prefix plus the length of every item
+pub fn mt_serialized_seq_len_with<C: MtCfg, T: MtSerialize>(
+    iter: impl ExactSizeIterator + IntoIterator<Item = T>,
+    ctx: &mut SerializeCtx,
+) -> Result<Option<usize>, SerializeError> {
+    let mut len = Some(len_prefix::<C>(iter.len())?);
+
+    for (i, item) in iter.into_iter().enumerate() {
+        let part = item.mt_serialized_len_with::<C::Inner>(ctx);
+        mt_add_len(&mut len, part).map_err(|e| e.at_index(i))?;
+    }
+
+    Ok(len)
 }
 
 pub fn mt_deserialize_seq<C: MtCfg, T: MtDeserialize>(
@@ -381,21 +905,95 @@ pub fn mt_deserialize_sized_seq<'a, C: MtCfg, T: MtDeserialize>(
 
     Ok(len
         .range()
-        .map_while(move |_| match T::mt_deserialize::<C::Inner>(reader) {
-            Err(DeserializeError::UnexpectedEof) if variable => None,
-            x => Some(x),
-        }))
+        .map_while(move |i| seq_item(variable, i, T::mt_deserialize::<C::Inner>(reader))))
+}
+
+pub fn mt_deserialize_seq_with<'a, 'u, C: MtCfg, T: MtDeserialize, R: Read>(
+    reader: &'a mut R,
+    ctx: &'a mut DeserializeCtx<'u>,
+) -> Result<
+    impl Iterator<Item = Result<T, DeserializeError>> + use<'a, 'u, C, T, R>,
+    DeserializeError,
+> {
+    let len = C::read_len(reader)?;
+    mt_deserialize_sized_seq_with::<C, _, _>(&len, reader, ctx)
+}
+
+pub fn mt_deserialize_sized_seq_with<'a, 'u, C: MtCfg, T: MtDeserialize, R: Read>(
+    len: &C::Len,
+    reader: &'a mut R,
+    ctx: &'a mut DeserializeCtx<'u>,
+) -> Result<
+    impl Iterator<Item = Result<T, DeserializeError>> + use<'a, 'u, C, T, R>,
+    DeserializeError,
+> {
+    let variable = len.option().is_none();
+
+    if let Some(x) = len.option() {
+        ctx.limits.check_len(x)?;
+    }
+
+    Ok(len.range().map_while(move |i| {
+        let item = ctx
+            .limits
+            .check_len(i + 1)
+            .and_then(|_| ctx.limits.alloc(core::mem::size_of::<T>()))
+            .and_then(|_| ctx.nested(|c| T::mt_deserialize_with::<C::Inner>(reader, c)));
+
+        seq_item(variable, i, item)
+    }))
+}
+
+// same as the unstable core::array::try_from_fn
+fn try_array<T, const N: usize>(
+    mut f: impl FnMut(usize) -> Result<T, DeserializeError>,
+) -> Result<[T; N], DeserializeError> {
+    let mut items = Vec::with_capacity(N);
+
+    for i in 0..N {
+        items.push(f(i)?);
+    }
+
+    Ok(items
+        .try_into()
+        .unwrap_or_else(|_| unreachable!("array length")))
+}
+
+// variable length sequences end at EOF
+fn seq_item<T>(
+    variable: bool,
+    index: usize,
+    item: Result<T, DeserializeError>,
+) -> Option<Result<T, DeserializeError>> {
+    match item {
+        Err(e) if variable && matches!(e.inner(), DeserializeError::UnexpectedEof) => None,
+        x => Some(x.map_err(|e| e.at_index(index))),
+    }
 }
 
 impl<T: MtSerialize, const N: usize> MtSerialize for [T; N] {
-    fn mt_serialize<C: MtCfg>(&self, writer: &mut impl Write) -> Result<(), SerializeError> {
-        mt_serialize_seq::<(), _>(writer, self.iter())
+    fn mt_serialize_with<C: MtCfg>(
+        &self,
+        writer: &mut impl Write,
+        ctx: &mut SerializeCtx,
+    ) -> Result<(), SerializeError> {
+        mt_serialize_seq_with::<(), _>(writer, self.iter(), ctx)
+    }
+
+    fn mt_serialized_len_with<C: MtCfg>(
+        &self,
+        ctx: &mut SerializeCtx,
+    ) -> Result<Option<usize>, SerializeError> {
+        mt_serialized_seq_len_with::<(), _>(self.iter(), ctx)
     }
 }
 
 impl<T: MtDeserialize, const N: usize> MtDeserialize for [T; N] {
-    fn mt_deserialize<C: MtCfg>(reader: &mut impl Read) -> Result<Self, DeserializeError> {
-        std::array::try_from_fn(|_| T::mt_deserialize::<DefCfg>(reader))
+    fn mt_deserialize_with<C: MtCfg>(
+        reader: &mut impl Read,
+        ctx: &mut DeserializeCtx,
+    ) -> Result<Self, DeserializeError> {
+        try_array(|i| T::mt_deserialize_with::<DefCfg>(reader, ctx).map_err(|e| e.at_index(i)))
     }
 }
 
@@ -403,6 +1001,13 @@ impl<T: MtSerialize, E: EnumSetTypeWithRepr<Repr = T>> MtSerialize for EnumSet<E
     fn mt_serialize<C: MtCfg>(&self, writer: &mut impl Write) -> Result<(), SerializeError> {
         self.as_repr().mt_serialize::<DefCfg>(writer)
     }
+
+    fn mt_serialized_len_with<C: MtCfg>(
+        &self,
+        ctx: &mut SerializeCtx,
+    ) -> Result<Option<usize>, SerializeError> {
+        self.as_repr().mt_serialized_len_with::<DefCfg>(ctx)
+    }
 }
 
 impl<T: MtDeserialize, E: EnumSetTypeWithRepr<Repr = T>> MtDeserialize for EnumSet<E> {
@@ -414,84 +1019,420 @@ impl<T: MtDeserialize, E: EnumSetTypeWithRepr<Repr = T>> MtDeserialize for EnumS
 }
 
 impl<T: MtSerialize> MtSerialize for Option<T> {
-    fn mt_serialize<C: MtCfg>(&self, writer: &mut impl Write) -> Result<(), SerializeError> {
+    fn mt_serialize_with<C: MtCfg>(
+        &self,
+        writer: &mut impl Write,
+        ctx: &mut SerializeCtx,
+    ) -> Result<(), SerializeError> {
         match self {
-            Some(item) => item.mt_serialize::<C>(writer),
+            Some(item) => item.mt_serialize_with::<C>(writer, ctx),
             None => Ok(()),
         }
     }
+
+    fn mt_serialized_len_with<C: MtCfg>(
+        &self,
+        ctx: &mut SerializeCtx,
+    ) -> Result<Option<usize>, SerializeError> {
+        match self {
+            Some(item) => item.mt_serialized_len_with::<C>(ctx),
+            None => Ok(Some(0)),
+        }
+    }
 }
 
 impl<T: MtDeserialize> MtDeserialize for Option<T> {
-    fn mt_deserialize<C: MtCfg>(reader: &mut impl Read) -> Result<Self, DeserializeError> {
-        T::mt_deserialize::<C>(reader).map(Some).or_default()
+    fn mt_deserialize_with<C: MtCfg>(
+        reader: &mut impl Read,
+        ctx: &mut DeserializeCtx,
+    ) -> Result<Self, DeserializeError> {
+        T::mt_deserialize_with::<C>(reader, ctx)
+            .map(Some)
+            .or_default()
+    }
+}
+
+impl<T: MtSerialize> MtSerialize for [T] {
+    fn mt_serialize_with<C: MtCfg>(
+        &self,
+        writer: &mut impl Write,
+        ctx: &mut SerializeCtx,
+    ) -> Result<(), SerializeError> {
+        mt_serialize_seq_with::<C, _>(writer, self.iter(), ctx)
+    }
+
+    fn mt_serialized_len_with<C: MtCfg>(
+        &self,
+        ctx: &mut SerializeCtx,
+    ) -> Result<Option<usize>, SerializeError> {
+        mt_serialized_seq_len_with::<C, _>(self.iter(), ctx)
     }
 }
 
 impl<T: MtSerialize> MtSerialize for Vec<T> {
-    fn mt_serialize<C: MtCfg>(&self, writer: &mut impl Write) -> Result<(), SerializeError> {
-        mt_serialize_seq::<C, _>(writer, self.iter())
+    fn mt_serialize_with<C: MtCfg>(
+        &self,
+        writer: &mut impl Write,
+        ctx: &mut SerializeCtx,
+    ) -> Result<(), SerializeError> {
+        mt_serialize_seq_with::<C, _>(writer, self.iter(), ctx)
+    }
+
+    fn mt_serialized_len_with<C: MtCfg>(
+        &self,
+        ctx: &mut SerializeCtx,
+    ) -> Result<Option<usize>, SerializeError> {
+        mt_serialized_seq_len_with::<C, _>(self.iter(), ctx)
     }
 }
 
 impl<T: MtDeserialize> MtDeserialize for Vec<T> {
-    fn mt_deserialize<C: MtCfg>(reader: &mut impl Read) -> Result<Self, DeserializeError> {
-        mt_deserialize_seq::<C, _>(reader)?.try_collect()
+    fn mt_deserialize_with<C: MtCfg>(
+        reader: &mut impl Read,
+        ctx: &mut DeserializeCtx,
+    ) -> Result<Self, DeserializeError> {
+        mt_deserialize_seq_with::<C, _, _>(reader, ctx)?.collect()
     }
 }
 
-impl<T: MtSerialize + std::cmp::Eq + std::hash::Hash> MtSerialize for HashSet<T> {
-    fn mt_serialize<C: MtCfg>(&self, writer: &mut impl Write) -> Result<(), SerializeError> {
-        mt_serialize_seq::<C, _>(writer, self.iter())
+impl<T: MtSerialize> MtSerialize for VecDeque<T> {
+    fn mt_serialize_with<C: MtCfg>(
+        &self,
+        writer: &mut impl Write,
+        ctx: &mut SerializeCtx,
+    ) -> Result<(), SerializeError> {
+        mt_serialize_seq_with::<C, _>(writer, self.iter(), ctx)
+    }
+
+    fn mt_serialized_len_with<C: MtCfg>(
+        &self,
+        ctx: &mut SerializeCtx,
+    ) -> Result<Option<usize>, SerializeError> {
+        mt_serialized_seq_len_with::<C, _>(self.iter(), ctx)
     }
 }
 
-impl<T: MtDeserialize + std::cmp::Eq + std::hash::Hash> MtDeserialize for HashSet<T> {
-    fn mt_deserialize<C: MtCfg>(reader: &mut impl Read) -> Result<Self, DeserializeError> {
-        mt_deserialize_seq::<C, _>(reader)?.try_collect()
+impl<T: MtDeserialize> MtDeserialize for VecDeque<T> {
+    fn mt_deserialize_with<C: MtCfg>(
+        reader: &mut impl Read,
+        ctx: &mut DeserializeCtx,
+    ) -> Result<Self, DeserializeError> {
+        mt_deserialize_seq_with::<C, _, _>(reader, ctx)?.collect()
     }
 }
 
-// TODO: support more tuples
-impl<A: MtSerialize, B: MtSerialize> MtSerialize for (A, B) {
-    fn mt_serialize<C: MtCfg>(&self, writer: &mut impl Write) -> Result<(), SerializeError> {
-        self.0.mt_serialize::<C>(writer)?;
-        self.1.mt_serialize::<C::Inner>(writer)?;
+#[cfg(feature = "std")]
+impl<T: MtSerialize + Eq + Hash, S> MtSerialize for HashSet<T, S> {
+    fn mt_serialize_with<C: MtCfg>(
+        &self,
+        writer: &mut impl Write,
+        ctx: &mut SerializeCtx,
+    ) -> Result<(), SerializeError> {
+        mt_serialize_seq_with::<C, _>(writer, self.iter(), ctx)
+    }
 
-        Ok(())
+    fn mt_serialized_len_with<C: MtCfg>(
+        &self,
+        ctx: &mut SerializeCtx,
+    ) -> Result<Option<usize>, SerializeError> {
+        mt_serialized_seq_len_with::<C, _>(self.iter(), ctx)
     }
 }
 
-impl<A: MtDeserialize, B: MtDeserialize> MtDeserialize for (A, B) {
-    fn mt_deserialize<C: MtCfg>(reader: &mut impl Read) -> Result<Self, DeserializeError> {
-        let a = A::mt_deserialize::<C>(reader)?;
-        let b = B::mt_deserialize::<C::Inner>(reader)?;
+#[cfg(feature = "std")]
+impl<T, S> MtDeserialize for HashSet<T, S>
+where
+    T: MtDeserialize + Eq + Hash,
+    S: BuildHasher + Default,
+{
+    fn mt_deserialize_with<C: MtCfg>(
+        reader: &mut impl Read,
+        ctx: &mut DeserializeCtx,
+    ) -> Result<Self, DeserializeError> {
+        mt_deserialize_seq_with::<C, _, _>(reader, ctx)?.collect()
+    }
+}
 
-        Ok((a, b))
+impl<T: MtSerialize + Ord> MtSerialize for BTreeSet<T> {
+    fn mt_serialize_with<C: MtCfg>(
+        &self,
+        writer: &mut impl Write,
+        ctx: &mut SerializeCtx,
+    ) -> Result<(), SerializeError> {
+        mt_serialize_seq_with::<C, _>(writer, self.iter(), ctx)
+    }
+
+    fn mt_serialized_len_with<C: MtCfg>(
+        &self,
+        ctx: &mut SerializeCtx,
+    ) -> Result<Option<usize>, SerializeError> {
+        mt_serialized_seq_len_with::<C, _>(self.iter(), ctx)
+    }
+}
+
+impl<T: MtDeserialize + Ord> MtDeserialize for BTreeSet<T> {
+    fn mt_deserialize_with<C: MtCfg>(
+        reader: &mut impl Read,
+        ctx: &mut DeserializeCtx,
+    ) -> Result<Self, DeserializeError> {
+        mt_deserialize_seq_with::<C, _, _>(reader, ctx)?.collect()
     }
 }
 
-impl<K, V> MtSerialize for HashMap<K, V>
+#[cfg(feature = "indexmap")]
+impl<T: MtSerialize + Eq + Hash, S> MtSerialize for IndexSet<T, S> {
+    fn mt_serialize_with<C: MtCfg>(
+        &self,
+        writer: &mut impl Write,
+        ctx: &mut SerializeCtx,
+    ) -> Result<(), SerializeError> {
+        mt_serialize_seq_with::<C, _>(writer, self.iter(), ctx)
+    }
+
+    fn mt_serialized_len_with<C: MtCfg>(
+        &self,
+        ctx: &mut SerializeCtx,
+    ) -> Result<Option<usize>, SerializeError> {
+        mt_serialized_seq_len_with::<C, _>(self.iter(), ctx)
+    }
+}
+
+#[cfg(feature = "indexmap")]
+impl<T, S> MtDeserialize for IndexSet<T, S>
 where
-    K: MtSerialize + std::cmp::Eq + std::hash::Hash,
+    T: MtDeserialize + Eq + Hash,
+    S: BuildHasher + Default,
+{
+    fn mt_deserialize_with<C: MtCfg>(
+        reader: &mut impl Read,
+        ctx: &mut DeserializeCtx,
+    ) -> Result<Self, DeserializeError> {
+        mt_deserialize_seq_with::<C, _, _>(reader, ctx)?.collect()
+    }
+}
+
+// each element is encoded with the Inner config of the one before it,
+// i.e. a pair uses C for its first element and C::Inner for its second
+macro_rules! ser_tuple {
+    (@call sync $x:expr, $C:ty, ($writer:ident, $ctx:ident)) => {
+        $x.mt_serialize_with::<$C>($writer, $ctx)?
+    };
+    (@call async $x:expr, $C:ty, ($writer:ident, $ctx:ident)) => {
+        $x.mt_serialize_async_with::<$C, _>($writer, $ctx).await?
+    };
+    (@call len $x:expr, $C:ty, ($len:ident, $ctx:ident)) => {
+        mt_add_len(&mut $len, $x.mt_serialized_len_with::<$C>($ctx))?
+    };
+    ($mode:ident $self:ident $args:tt $C:ty;) => {};
+    ($mode:ident $self:ident $args:tt $C:ty; $i:tt $($rest:tt)*) => {
+        ser_tuple!(@call $mode $self.$i, $C, $args);
+        ser_tuple!($mode $self $args <$C as MtCfg>::Inner; $($rest)*);
+    };
+}
+
+macro_rules! des_tuple {
+    (@call owned $T:ident $C:ty, ($reader:ident, $ctx:ident)) => {
+        $T::mt_deserialize_with::<$C>($reader, $ctx)?
+    };
+    (@call borrowed $T:ident $C:ty, ($reader:ident, $ctx:ident)) => {
+        $T::mt_deserialize_borrowed_with::<$C>($reader, $ctx)?
+    };
+    (@call async $T:ident $C:ty, ($reader:ident, $ctx:ident)) => {
+        $T::mt_deserialize_async_with::<$C, _>($reader, $ctx).await?
+    };
+    ($mode:ident $args:tt $C:ty;) => {};
+    ($mode:ident $args:tt $C:ty; $T:ident $x:ident $($rest:tt)*) => {
+        let $x = des_tuple!(@call $mode $T $C, $args);
+        des_tuple!($mode $args <$C as MtCfg>::Inner; $($rest)*);
+    };
+}
+
+macro_rules! impl_tuple {
+    ($($T:ident $x:ident $i:tt),+) => {
+        impl<$($T: MtSerialize),+> MtSerialize for ($($T,)+) {
+            fn mt_serialize_with<C: MtCfg>(
+                &self,
+                writer: &mut impl Write,
+                ctx: &mut SerializeCtx,
+            ) -> Result<(), SerializeError> {
+                ser_tuple!(sync self (writer, ctx) C; $($i)+);
+                Ok(())
+            }
+
+            fn mt_serialized_len_with<C: MtCfg>(
+                &self,
+                ctx: &mut SerializeCtx,
+            ) -> Result<Option<usize>, SerializeError> {
+                let mut len = Some(0);
+                ser_tuple!(len self (len, ctx) C; $($i)+);
+                Ok(len)
+            }
+        }
+
+        #[cfg(feature = "async")]
+        impl<$($T: MtAsyncSerialize),+> MtAsyncSerialize for ($($T,)+) {
+            async fn mt_serialize_async_with<
+                C: MtCfg,
+                W: tokio::io::AsyncWrite + Unpin + Send,
+            >(
+                &self,
+                writer: &mut W,
+                ctx: &mut SerializeCtx<'_>,
+            ) -> Result<(), SerializeError> {
+                ser_tuple!(async self (writer, ctx) C; $($i)+);
+                Ok(())
+            }
+        }
+
+        impl<$($T: MtDeserialize),+> MtDeserialize for ($($T,)+) {
+            fn mt_deserialize_with<C: MtCfg>(
+                reader: &mut impl Read,
+                ctx: &mut DeserializeCtx,
+            ) -> Result<Self, DeserializeError> {
+                des_tuple!(owned (reader, ctx) C; $($T $x)+);
+                Ok(($($x,)+))
+            }
+        }
+
+        impl<'a, $($T: MtDeserializeBorrowed<'a>),+> MtDeserializeBorrowed<'a> for ($($T,)+) {
+            fn mt_deserialize_borrowed_with<C: MtCfg>(
+                reader: &mut &'a [u8],
+                ctx: &mut DeserializeCtx,
+            ) -> Result<Self, DeserializeError> {
+                des_tuple!(borrowed (reader, ctx) C; $($T $x)+);
+                Ok(($($x,)+))
+            }
+        }
+
+        #[cfg(feature = "async")]
+        impl<$($T: MtAsyncDeserialize),+> MtAsyncDeserialize for ($($T,)+) {
+            async fn mt_deserialize_async_with<
+                C: MtCfg,
+                R: tokio::io::AsyncRead + Unpin + Send,
+            >(
+                reader: &mut R,
+                ctx: &mut DeserializeCtx<'_>,
+            ) -> Result<Self, DeserializeError> {
+                des_tuple!(async (reader, ctx) C; $($T $x)+);
+                Ok(($($x,)+))
+            }
+        }
+    };
+}
+
+impl_tuple!(A a 0, B b 1);
+impl_tuple!(A a 0, B b 1, D d 2);
+impl_tuple!(A a 0, B b 1, D d 2, E e 3);
+impl_tuple!(A a 0, B b 1, D d 2, E e 3, F f 4);
+impl_tuple!(A a 0, B b 1, D d 2, E e 3, F f 4, G g 5);
+impl_tuple!(A a 0, B b 1, D d 2, E e 3, F f 4, G g 5, H h 6);
+impl_tuple!(A a 0, B b 1, D d 2, E e 3, F f 4, G g 5, H h 6, I i 7);
+impl_tuple!(A a 0, B b 1, D d 2, E e 3, F f 4, G g 5, H h 6, I i 7, J j 8);
+impl_tuple!(A a 0, B b 1, D d 2, E e 3, F f 4, G g 5, H h 6, I i 7, J j 8, K k 9);
+impl_tuple!(A a 0, B b 1, D d 2, E e 3, F f 4, G g 5, H h 6, I i 7, J j 8, K k 9, L l 10);
+impl_tuple!(A a 0, B b 1, D d 2, E e 3, F f 4, G g 5, H h 6, I i 7, J j 8, K k 9, L l 10, M m 11);
+
+#[cfg(feature = "std")]
+impl<K, V, S> MtSerialize for HashMap<K, V, S>
+where
+    K: MtSerialize + Eq + Hash,
     V: MtSerialize,
 {
-    fn mt_serialize<C: MtCfg>(&self, writer: &mut impl Write) -> Result<(), SerializeError> {
-        mt_serialize_seq::<C, _>(writer, self.iter())
+    fn mt_serialize_with<C: MtCfg>(
+        &self,
+        writer: &mut impl Write,
+        ctx: &mut SerializeCtx,
+    ) -> Result<(), SerializeError> {
+        mt_serialize_seq_with::<C, _>(writer, self.iter(), ctx)
+    }
+
+    fn mt_serialized_len_with<C: MtCfg>(
+        &self,
+        ctx: &mut SerializeCtx,
+    ) -> Result<Option<usize>, SerializeError> {
+        mt_serialized_seq_len_with::<C, _>(self.iter(), ctx)
     }
 }
 
-impl<K, V> MtDeserialize for HashMap<K, V>
+#[cfg(feature = "std")]
+impl<K, V, S> MtDeserialize for HashMap<K, V, S>
 where
-    K: MtDeserialize + std::cmp::Eq + std::hash::Hash,
+    K: MtDeserialize + Eq + Hash,
     V: MtDeserialize,
+    S: BuildHasher + Default,
 {
-    fn mt_deserialize<C: MtCfg>(reader: &mut impl Read) -> Result<Self, DeserializeError> {
-        mt_deserialize_seq::<C, _>(reader)?.try_collect()
+    fn mt_deserialize_with<C: MtCfg>(
+        reader: &mut impl Read,
+        ctx: &mut DeserializeCtx,
+    ) -> Result<Self, DeserializeError> {
+        mt_deserialize_seq_with::<C, _, _>(reader, ctx)?.collect()
+    }
+}
+
+impl<K: MtSerialize + Ord, V: MtSerialize> MtSerialize for BTreeMap<K, V> {
+    fn mt_serialize_with<C: MtCfg>(
+        &self,
+        writer: &mut impl Write,
+        ctx: &mut SerializeCtx,
+    ) -> Result<(), SerializeError> {
+        mt_serialize_seq_with::<C, _>(writer, self.iter(), ctx)
+    }
+
+    fn mt_serialized_len_with<C: MtCfg>(
+        &self,
+        ctx: &mut SerializeCtx,
+    ) -> Result<Option<usize>, SerializeError> {
+        mt_serialized_seq_len_with::<C, _>(self.iter(), ctx)
+    }
+}
+
+impl<K: MtDeserialize + Ord, V: MtDeserialize> MtDeserialize for BTreeMap<K, V> {
+    fn mt_deserialize_with<C: MtCfg>(
+        reader: &mut impl Read,
+        ctx: &mut DeserializeCtx,
+    ) -> Result<Self, DeserializeError> {
+        mt_deserialize_seq_with::<C, _, _>(reader, ctx)?.collect()
+    }
+}
+
+#[cfg(feature = "indexmap")]
+impl<K, V, S> MtSerialize for IndexMap<K, V, S>
+where
+    K: MtSerialize + Eq + Hash,
+    V: MtSerialize,
+{
+    fn mt_serialize_with<C: MtCfg>(
+        &self,
+        writer: &mut impl Write,
+        ctx: &mut SerializeCtx,
+    ) -> Result<(), SerializeError> {
+        mt_serialize_seq_with::<C, _>(writer, self.iter(), ctx)
+    }
+
+    fn mt_serialized_len_with<C: MtCfg>(
+        &self,
+        ctx: &mut SerializeCtx,
+    ) -> Result<Option<usize>, SerializeError> {
+        mt_serialized_seq_len_with::<C, _>(self.iter(), ctx)
     }
 }
 
-impl MtSerialize for &str {
+#[cfg(feature = "indexmap")]
+impl<K, V, S> MtDeserialize for IndexMap<K, V, S>
+where
+    K: MtDeserialize + Eq + Hash,
+    V: MtDeserialize,
+    S: BuildHasher + Default,
+{
+    fn mt_deserialize_with<C: MtCfg>(
+        reader: &mut impl Read,
+        ctx: &mut DeserializeCtx,
+    ) -> Result<Self, DeserializeError> {
+        mt_deserialize_seq_with::<C, _, _>(reader, ctx)?.collect()
+    }
+}
+
+impl MtSerialize for str {
     fn mt_serialize<C: MtCfg>(&self, writer: &mut impl Write) -> Result<(), SerializeError> {
         if C::utf16() {
             self.encode_utf16()
@@ -501,28 +1442,53 @@ impl MtSerialize for &str {
             mt_serialize_seq::<C, _>(writer, self.as_bytes().iter())
         }
     }
+
+    fn mt_serialized_len_with<C: MtCfg>(
+        &self,
+        _ctx: &mut SerializeCtx,
+    ) -> Result<Option<usize>, SerializeError> {
+        let (len, bytes) = if C::utf16() {
+            let units = self.encode_utf16().count();
+            (units, 2 * units)
+        } else {
+            (self.len(), self.len())
+        };
+
+        Ok(Some(len_prefix::<C>(len)? + bytes))
+    }
 }
 
 impl MtSerialize for String {
     fn mt_serialize<C: MtCfg>(&self, writer: &mut impl Write) -> Result<(), SerializeError> {
         self.as_str().mt_serialize::<C>(writer)
     }
+
+    fn mt_serialized_len_with<C: MtCfg>(
+        &self,
+        ctx: &mut SerializeCtx,
+    ) -> Result<Option<usize>, SerializeError> {
+        self.as_str().mt_serialized_len_with::<C>(ctx)
+    }
 }
 
 impl MtDeserialize for String {
-    fn mt_deserialize<C: MtCfg>(reader: &mut impl Read) -> Result<Self, DeserializeError> {
+    fn mt_deserialize_with<C: MtCfg>(
+        reader: &mut impl Read,
+        ctx: &mut DeserializeCtx,
+    ) -> Result<Self, DeserializeError> {
         if C::utf16() {
             let mut err = None;
 
-            let res =
-                char::decode_utf16(mt_deserialize_seq::<C, _>(reader)?.map_while(|x| match x {
+            let res: Result<String, _> = char::decode_utf16(
+                mt_deserialize_seq_with::<C, _, _>(reader, ctx)?.map_while(|x| match x {
                     Ok(v) => Some(v),
                     Err(e) => {
                         err = Some(e);
                         None
                     }
-                }))
-                .try_collect();
+                }),
+            )
+            .collect();
 
             match err {
                 None => Ok(res?),
@@ -533,30 +1499,115 @@ impl MtDeserialize for String {
 
             // use capacity if available
             let mut st = match len.option() {
-                Some(x) => String::with_capacity(x),
+                Some(x) => {
+                    ctx.limits.check_len(x)?;
+                    ctx.limits.alloc(x)?;
+                    String::with_capacity(x)
+                }
                 None => String::new(),
             };
 
             len.take(WrapRead(reader)).read_to_string(&mut st)?;
 
+            // without a length the string grows with the input, account for it afterwards
+            if len.option().is_none() {
+                ctx.limits.check_len(st.len())?;
+                ctx.limits.alloc(st.len())?;
+            }
+
             Ok(st)
         }
     }
 }
 
-impl<T: MtSerialize> MtSerialize for Box<T> {
-    fn mt_serialize<C: MtCfg>(&self, writer: &mut impl Write) -> Result<(), SerializeError> {
-        self.deref().mt_serialize::<C>(writer)
+macro_rules! impl_ptr {
+    ($P:ident) => {
+        impl<T: MtSerialize + ?Sized> MtSerialize for $P<T> {
+            fn mt_serialize_with<C: MtCfg>(
+                &self,
+                writer: &mut impl Write,
+                ctx: &mut SerializeCtx,
+            ) -> Result<(), SerializeError> {
+                self.deref().mt_serialize_with::<C>(writer, ctx)
+            }
+
+            fn mt_serialized_len_with<C: MtCfg>(
+                &self,
+                ctx: &mut SerializeCtx,
+            ) -> Result<Option<usize>, SerializeError> {
+                self.deref().mt_serialized_len_with::<C>(ctx)
+            }
+        }
+
+        impl<T: MtDeserialize> MtDeserialize for $P<T> {
+            fn mt_deserialize_with<C: MtCfg>(
+                reader: &mut impl Read,
+                ctx: &mut DeserializeCtx,
+            ) -> Result<Self, DeserializeError> {
+                ctx.limits.alloc(core::mem::size_of::<T>())?;
+                let item = ctx.nested(|c| T::mt_deserialize_with::<C>(reader, c))?;
+
+                Ok(Self::new(item))
+            }
+        }
+
+        impl<T: MtDeserialize> MtDeserialize for $P<[T]> {
+            fn mt_deserialize_with<C: MtCfg>(
+                reader: &mut impl Read,
+                ctx: &mut DeserializeCtx,
+            ) -> Result<Self, DeserializeError> {
+                Ok(Vec::<T>::mt_deserialize_with::<C>(reader, ctx)?.into())
+            }
+        }
+
+        impl MtDeserialize for $P<str> {
+            fn mt_deserialize_with<C: MtCfg>(
+                reader: &mut impl Read,
+                ctx: &mut DeserializeCtx,
+            ) -> Result<Self, DeserializeError> {
+                Ok(String::mt_deserialize_with::<C>(reader, ctx)?.into())
+            }
+        }
+    };
+}
+
+impl_ptr!(Box);
+impl_ptr!(Rc);
+impl_ptr!(Arc);
+
+impl<T: MtSerialize + ToOwned + ?Sized> MtSerialize for Cow<'_, T> {
+    fn mt_serialize_with<C: MtCfg>(
+        &self,
+        writer: &mut impl Write,
+        ctx: &mut SerializeCtx,
+    ) -> Result<(), SerializeError> {
+        self.deref().mt_serialize_with::<C>(writer, ctx)
+    }
+
+    fn mt_serialized_len_with<C: MtCfg>(
+        &self,
+        ctx: &mut SerializeCtx,
+    ) -> Result<Option<usize>, SerializeError> {
+        self.deref().mt_serialized_len_with::<C>(ctx)
     }
 }
 
-impl<T: MtDeserialize> MtDeserialize for Box<T> {
-    fn mt_deserialize<C: MtCfg>(reader: &mut impl Read) -> Result<Self, DeserializeError> {
-        Ok(Self::new(T::mt_deserialize::<C>(reader)?))
+impl<T: ToOwned + ?Sized> MtDeserialize for Cow<'_, T>
+where
+    T::Owned: MtDeserialize,
+{
+    fn mt_deserialize_with<C: MtCfg>(
+        reader: &mut impl Read,
+        ctx: &mut DeserializeCtx,
+    ) -> Result<Self, DeserializeError> {
+        Ok(Cow::Owned(T::Owned::mt_deserialize_with::<C>(reader, ctx)?))
     }
 }
 
-#[derive(MtSerialize, MtDeserialize)]
+#[derive(MtSerialize, MtDeserialize, MtDeserializeBorrowed)]
+#[cfg_attr(feature = "async", derive(MtAsyncSerialize, MtAsyncDeserialize))]
+#[cfg_attr(feature = "proptest", derive(MtStrategy))]
+#[cfg_attr(feature = "random", derive(GenerateRandom))]
 #[mt(typename = "Range")]
 #[allow(unused)]
 struct RemoteRange<T> {
@@ -564,59 +1615,97 @@ struct RemoteRange<T> {
     end: T,
 }
 
-#[derive(MtSerialize, MtDeserialize)]
+#[derive(MtSerialize, MtDeserialize, MtDeserializeBorrowed)]
+#[cfg_attr(feature = "async", derive(MtAsyncSerialize, MtAsyncDeserialize))]
+#[cfg_attr(feature = "proptest", derive(MtStrategy))]
+#[cfg_attr(feature = "random", derive(GenerateRandom))]
 #[mt(typename = "RangeFrom")]
 #[allow(unused)]
 struct RemoteRangeFrom<T> {
     start: T,
 }
 
-#[derive(MtSerialize, MtDeserialize)]
+#[derive(MtSerialize, MtDeserialize, MtDeserializeBorrowed)]
+#[cfg_attr(feature = "async", derive(MtAsyncSerialize, MtAsyncDeserialize))]
+#[cfg_attr(feature = "proptest", derive(MtStrategy))]
+#[cfg_attr(feature = "random", derive(GenerateRandom))]
 #[mt(typename = "RangeFull")]
 #[allow(unused)]
 struct RemoteRangeFull;
 
 // RangeInclusive fields are private
 impl<T: MtSerialize> MtSerialize for RangeInclusive<T> {
-    fn mt_serialize<C: MtCfg>(&self, writer: &mut impl Write) -> Result<(), SerializeError> {
-        self.start().mt_serialize::<DefCfg>(writer)?;
-        self.end().mt_serialize::<DefCfg>(writer)?;
+    fn mt_serialize_with<C: MtCfg>(
+        &self,
+        writer: &mut impl Write,
+        ctx: &mut SerializeCtx,
+    ) -> Result<(), SerializeError> {
+        self.start().mt_serialize_with::<DefCfg>(writer, ctx)?;
+        self.end().mt_serialize_with::<DefCfg>(writer, ctx)?;
 
         Ok(())
     }
+
+    fn mt_serialized_len_with<C: MtCfg>(
+        &self,
+        ctx: &mut SerializeCtx,
+    ) -> Result<Option<usize>, SerializeError> {
+        let mut len = Some(0);
+        mt_add_len(&mut len, self.start().mt_serialized_len_with::<DefCfg>(ctx))?;
+        mt_add_len(&mut len, self.end().mt_serialized_len_with::<DefCfg>(ctx))?;
+
+        Ok(len)
+    }
 }
 
 impl<T: MtDeserialize> MtDeserialize for RangeInclusive<T> {
-    fn mt_deserialize<C: MtCfg>(reader: &mut impl Read) -> Result<Self, DeserializeError> {
-        let start = T::mt_deserialize::<DefCfg>(reader)?;
-        let end = T::mt_deserialize::<DefCfg>(reader)?;
+    fn mt_deserialize_with<C: MtCfg>(
+        reader: &mut impl Read,
+        ctx: &mut DeserializeCtx,
+    ) -> Result<Self, DeserializeError> {
+        let start = T::mt_deserialize_with::<DefCfg>(reader, ctx)?;
+        let end = T::mt_deserialize_with::<DefCfg>(reader, ctx)?;
 
         Ok(start..=end)
     }
 }
 
-#[derive(MtSerialize, MtDeserialize)]
+#[derive(MtSerialize, MtDeserialize, MtDeserializeBorrowed)]
+#[cfg_attr(feature = "async", derive(MtAsyncSerialize, MtAsyncDeserialize))]
+#[cfg_attr(feature = "proptest", derive(MtStrategy))]
+#[cfg_attr(feature = "random", derive(GenerateRandom))]
 #[mt(typename = "RangeTo")]
 #[allow(unused)]
 struct RemoteRangeTo<T> {
     end: T,
 }
 
-#[derive(MtSerialize, MtDeserialize)]
+#[derive(MtSerialize, MtDeserialize, MtDeserializeBorrowed)]
+#[cfg_attr(feature = "async", derive(MtAsyncSerialize, MtAsyncDeserialize))]
+#[cfg_attr(feature = "proptest", derive(MtStrategy))]
+#[cfg_attr(feature = "random", derive(GenerateRandom))]
 #[mt(typename = "RangeToInclusive")]
 #[allow(unused)]
 struct RemoteRangeToInclusive<T> {
     end: T,
 }
 
-#[derive(MtSerialize, MtDeserialize)]
+#[cfg(feature = "cgmath")]
+#[derive(MtSerialize, MtDeserialize, MtDeserializeBorrowed)]
+#[cfg_attr(feature = "async", derive(MtAsyncSerialize, MtAsyncDeserialize))]
+#[cfg_attr(feature = "proptest", derive(MtStrategy))]
+#[cfg_attr(feature = "random", derive(GenerateRandom))]
 #[mt(typename = "Vector1")]
 #[allow(unused)]
 struct RemoteVector1<T> {
     x: T,
 }
 
-#[derive(MtSerialize, MtDeserialize)]
+#[cfg(feature = "cgmath")]
+#[derive(MtSerialize, MtDeserialize, MtDeserializeBorrowed)]
+#[cfg_attr(feature = "async", derive(MtAsyncSerialize, MtAsyncDeserialize))]
+#[cfg_attr(feature = "proptest", derive(MtStrategy))]
+#[cfg_attr(feature = "random", derive(GenerateRandom))]
 #[mt(typename = "Vector2")]
 #[allow(unused)]
 struct RemoteVector2<T> {
@@ -624,7 +1713,11 @@ struct RemoteVector2<T> {
     y: T,
 }
 
-#[derive(MtSerialize, MtDeserialize)]
+#[cfg(feature = "cgmath")]
+#[derive(MtSerialize, MtDeserialize, MtDeserializeBorrowed)]
+#[cfg_attr(feature = "async", derive(MtAsyncSerialize, MtAsyncDeserialize))]
+#[cfg_attr(feature = "proptest", derive(MtStrategy))]
+#[cfg_attr(feature = "random", derive(GenerateRandom))]
 #[mt(typename = "Vector3")]
 #[allow(unused)]
 struct RemoteVector3<T> {
@@ -633,7 +1726,11 @@ struct RemoteVector3<T> {
     z: T,
 }
 
-#[derive(MtSerialize, MtDeserialize)]
+#[cfg(feature = "cgmath")]
+#[derive(MtSerialize, MtDeserialize, MtDeserializeBorrowed)]
+#[cfg_attr(feature = "async", derive(MtAsyncSerialize, MtAsyncDeserialize))]
+#[cfg_attr(feature = "proptest", derive(MtStrategy))]
+#[cfg_attr(feature = "random", derive(GenerateRandom))]
 #[mt(typename = "Vector4")]
 #[allow(unused)]
 struct RemoteVector4<T> {
@@ -643,14 +1740,22 @@ struct RemoteVector4<T> {
     w: T,
 }
 
-#[derive(MtSerialize, MtDeserialize)]
+#[cfg(feature = "cgmath")]
+#[derive(MtSerialize, MtDeserialize, MtDeserializeBorrowed)]
+#[cfg_attr(feature = "async", derive(MtAsyncSerialize, MtAsyncDeserialize))]
+#[cfg_attr(feature = "proptest", derive(MtStrategy))]
+#[cfg_attr(feature = "random", derive(GenerateRandom))]
 #[mt(typename = "Point1")]
 #[allow(unused)]
 struct RemotePoint1<T> {
     x: T,
 }
 
-#[derive(MtSerialize, MtDeserialize)]
+#[cfg(feature = "cgmath")]
+#[derive(MtSerialize, MtDeserialize, MtDeserializeBorrowed)]
+#[cfg_attr(feature = "async", derive(MtAsyncSerialize, MtAsyncDeserialize))]
+#[cfg_attr(feature = "proptest", derive(MtStrategy))]
+#[cfg_attr(feature = "random", derive(GenerateRandom))]
 #[mt(typename = "Point2")]
 #[allow(unused)]
 struct RemotePoint2<T> {
@@ -658,7 +1763,11 @@ struct RemotePoint2<T> {
     y: T,
 }
 
-#[derive(MtSerialize, MtDeserialize)]
+#[cfg(feature = "cgmath")]
+#[derive(MtSerialize, MtDeserialize, MtDeserializeBorrowed)]
+#[cfg_attr(feature = "async", derive(MtAsyncSerialize, MtAsyncDeserialize))]
+#[cfg_attr(feature = "proptest", derive(MtStrategy))]
+#[cfg_attr(feature = "random", derive(GenerateRandom))]
 #[mt(typename = "Point3")]
 #[allow(unused)]
 struct RemotePoint3<T> {
@@ -667,17 +1776,29 @@ struct RemotePoint3<T> {
     z: T,
 }
 
-#[derive(MtSerialize, MtDeserialize)]
+#[cfg(feature = "cgmath")]
+#[derive(MtSerialize, MtDeserialize, MtDeserializeBorrowed)]
+#[cfg_attr(feature = "async", derive(MtAsyncSerialize, MtAsyncDeserialize))]
+#[cfg_attr(feature = "proptest", derive(MtStrategy))]
+#[cfg_attr(feature = "random", derive(GenerateRandom))]
 #[mt(typename = "Deg")]
 #[allow(unused)]
 struct RemoteDeg<T>(T);
 
-#[derive(MtSerialize, MtDeserialize)]
+#[cfg(feature = "cgmath")]
+#[derive(MtSerialize, MtDeserialize, MtDeserializeBorrowed)]
+#[cfg_attr(feature = "async", derive(MtAsyncSerialize, MtAsyncDeserialize))]
+#[cfg_attr(feature = "proptest", derive(MtStrategy))]
+#[cfg_attr(feature = "random", derive(GenerateRandom))]
 #[mt(typename = "Rad")]
 #[allow(unused)]
 struct RemoteRad<T>(T);
 
-#[derive(MtSerialize, MtDeserialize)]
+#[cfg(feature = "cgmath")]
+#[derive(MtSerialize, MtDeserialize, MtDeserializeBorrowed)]
+#[cfg_attr(feature = "async", derive(MtAsyncSerialize, MtAsyncDeserialize))]
+#[cfg_attr(feature = "proptest", derive(MtStrategy))]
+#[cfg_attr(feature = "random", derive(GenerateRandom))]
 #[mt(typename = "Euler")]
 #[allow(unused)]
 struct RemoteEuler<T> {
@@ -686,7 +1807,10 @@ struct RemoteEuler<T> {
     z: T,
 }
 
-#[derive(MtSerialize, MtDeserialize)]
+#[cfg(feature = "collision")]
+#[derive(MtSerialize, MtDeserialize, MtDeserializeBorrowed)]
+#[cfg_attr(feature = "async", derive(MtAsyncSerialize, MtAsyncDeserialize))]
+#[cfg_attr(feature = "random", derive(GenerateRandom))]
 #[mt(typename = "Aabb2")]
 #[allow(unused)]
 struct RemoteAabb2<T> {
@@ -694,7 +1818,10 @@ struct RemoteAabb2<T> {
     max: Point2<T>,
 }
 
-#[derive(MtSerialize, MtDeserialize)]
+#[cfg(feature = "collision")]
+#[derive(MtSerialize, MtDeserialize, MtDeserializeBorrowed)]
+#[cfg_attr(feature = "async", derive(MtAsyncSerialize, MtAsyncDeserialize))]
+#[cfg_attr(feature = "random", derive(GenerateRandom))]
 #[mt(typename = "Aabb3")]
 #[allow(unused)]
 struct RemoteAabb3<T> {
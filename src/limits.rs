@@ -0,0 +1,198 @@
+use super::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    Len,
+    Bytes,
+    Depth,
+    Decompressed,
+}
+
+impl core::fmt::Display for Limit {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{self:?} limit exceeded")
+    }
+}
+
+// lets a limit pass through io::Error, see LimitRead
+impl core::error::Error for Limit {}
+
+// caps for a single deserialization, carried by DeserializeCtx
+#[derive(Debug, Clone)]
+pub struct Limits {
+    pub max_len: usize,        // elements per sequence, bytes per string
+    pub max_bytes: usize,      // total bytes allocated for sequences and strings
+    pub max_depth: usize,      // nesting of sequences and pointers
+    pub max_decompressed: u64, // output of a zlib/zstd section without max_decompressed
+    bytes: usize,
+    depth: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self::new(usize::MAX, usize::MAX, usize::MAX)
+    }
+}
+
+impl Limits {
+    pub fn new(max_len: usize, max_bytes: usize, max_depth: usize) -> Self {
+        Self {
+            max_len,
+            max_bytes,
+            max_depth,
+            max_decompressed: u64::MAX,
+            bytes: 0,
+            depth: 0,
+        }
+    }
+
+    // bytes accounted for so far
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    pub fn check_len(&self, len: usize) -> Result<(), DeserializeError> {
+        if len > self.max_len {
+            return Err(DeserializeError::LimitExceeded(Limit::Len));
+        }
+
+        Ok(())
+    }
+
+    pub fn alloc(&mut self, bytes: usize) -> Result<(), DeserializeError> {
+        match self.bytes.checked_add(bytes) {
+            Some(x) if x <= self.max_bytes => {
+                self.bytes = x;
+                Ok(())
+            }
+            _ => Err(DeserializeError::LimitExceeded(Limit::Bytes)),
+        }
+    }
+
+    // runs f one level deeper
+    pub fn nested<T>(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> Result<T, DeserializeError>,
+    ) -> Result<T, DeserializeError> {
+        self.enter()?;
+        let res = f(self);
+        self.leave();
+
+        res
+    }
+
+    pub(crate) fn enter(&mut self) -> Result<(), DeserializeError> {
+        if self.depth >= self.max_depth {
+            return Err(DeserializeError::LimitExceeded(Limit::Depth));
+        }
+
+        self.depth += 1;
+        Ok(())
+    }
+
+    pub(crate) fn leave(&mut self) {
+        self.depth -= 1;
+    }
+}
+
+// fails once more than limit bytes come out of the inner reader, used to cap
+// decompressed sections (the error converts to DeserializeError::LimitExceeded)
+#[cfg(feature = "std")]
+pub struct LimitRead<R> {
+    inner: R,
+    limit: u64,
+    left: u64,
+}
+
+#[cfg(feature = "std")]
+impl<R> LimitRead<R> {
+    pub fn new(inner: R, limit: u64) -> Self {
+        Self {
+            inner,
+            limit,
+            left: limit,
+        }
+    }
+
+    // bytes that came out of the inner reader so far
+    pub fn pos(&self) -> u64 {
+        self.limit - self.left
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    fn exceeded() -> io::Error {
+        io::Error::other(Limit::Decompressed)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: Read> Read for LimitRead<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        // only an error if there actually is more
+        if self.left == 0 {
+            return match self.inner.read(&mut [0])? {
+                0 => Ok(0),
+                _ => Err(Self::exceeded()),
+            };
+        }
+
+        let max = buf.len().min(self.left.try_into().unwrap_or(usize::MAX));
+        let n = self.inner.read(&mut buf[..max])?;
+        self.left -= n as u64;
+
+        Ok(n)
+    }
+}
+
+#[cfg(feature = "async")]
+impl<R: tokio::io::AsyncRead + Unpin> tokio::io::AsyncRead for LimitRead<R> {
+    fn poll_read(
+        mut self: core::pin::Pin<&mut Self>,
+        cx: &mut core::task::Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> core::task::Poll<io::Result<()>> {
+        use core::{pin::Pin, task::Poll};
+        use tokio::io::ReadBuf;
+
+        let this = &mut *self;
+
+        if buf.remaining() == 0 {
+            return Poll::Ready(Ok(()));
+        }
+
+        if this.left == 0 {
+            let mut probe = [0];
+            let mut probe = ReadBuf::new(&mut probe);
+
+            return match Pin::new(&mut this.inner).poll_read(cx, &mut probe) {
+                Poll::Ready(Ok(())) if !probe.filled().is_empty() => {
+                    Poll::Ready(Err(Self::exceeded()))
+                }
+                x => x,
+            };
+        }
+
+        let max = buf
+            .remaining()
+            .min(this.left.try_into().unwrap_or(usize::MAX));
+        let mut limited = ReadBuf::new(buf.initialize_unfilled_to(max));
+
+        match Pin::new(&mut this.inner).poll_read(cx, &mut limited) {
+            Poll::Ready(Ok(())) => {
+                let n = limited.filled().len();
+                buf.advance(n);
+                this.left -= n as u64;
+
+                Poll::Ready(Ok(()))
+            }
+            x => x,
+        }
+    }
+}
@@ -0,0 +1,248 @@
+use super::*;
+use generate::{fit_string, max_bits, max_seq_len, MapEntry};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+// random values that mt_serialize::<C> accepts, i.e. sequences and strings fit into the
+// length prefix of C (enums pick one of their variants, so discriminants and string_repr
+// names are always valid). recursive types may generate very large values
+pub trait GenerateRandom: Sized {
+    fn generate_random<C: MtCfg>(rng: &mut impl Rng) -> Self;
+
+    // for values that more data follows. a None is only read back at the end of the input,
+    // so Option (and anything ending in one) is always Some here
+    fn generate_random_followed<C: MtCfg>(rng: &mut impl Rng) -> Self {
+        Self::generate_random::<C>(rng)
+    }
+}
+
+// the same seed gives the same values on every platform, for reproducible test corpora
+pub fn mt_seeded_rng(seed: u64) -> ChaCha8Rng {
+    ChaCha8Rng::seed_from_u64(seed)
+}
+
+pub fn mt_generate_seeded<C: MtCfg, T: GenerateRandom>(seed: u64) -> T {
+    T::generate_random::<C>(&mut mt_seeded_rng(seed))
+}
+
+macro_rules! impl_gen {
+    ($($T:ty),*) => {
+        $(
+            impl GenerateRandom for $T {
+                fn generate_random<C: MtCfg>(rng: &mut impl Rng) -> Self {
+                    rng.gen()
+                }
+            }
+        )*
+    };
+}
+
+impl_gen!(u8, i8, u16, i16, u32, i32, f32, u64, i64, f64, bool, ());
+
+impl<T: GenerateRandom, const N: usize> GenerateRandom for [T; N] {
+    fn generate_random<C: MtCfg>(rng: &mut impl Rng) -> Self {
+        core::array::from_fn(|_| T::generate_random::<DefCfg>(rng))
+    }
+}
+
+impl<T: GenerateRandom, E: EnumSetTypeWithRepr<Repr = T>> GenerateRandom for EnumSet<E> {
+    fn generate_random<C: MtCfg>(rng: &mut impl Rng) -> Self {
+        EnumSet::from_repr_truncated(T::generate_random::<DefCfg>(rng))
+    }
+}
+
+impl<T: GenerateRandom> GenerateRandom for Option<T> {
+    fn generate_random<C: MtCfg>(rng: &mut impl Rng) -> Self {
+        rng.gen::<bool>().then(|| T::generate_random::<C>(rng))
+    }
+
+    fn generate_random_followed<C: MtCfg>(rng: &mut impl Rng) -> Self {
+        Some(T::generate_random_followed::<C>(rng))
+    }
+}
+
+fn generate_seq<C: MtCfg, T: GenerateRandom, B: FromIterator<T>>(rng: &mut impl Rng) -> B {
+    let len = rng.gen_range(0..=max_seq_len::<C>());
+    (0..len)
+        .map(|_| T::generate_random_followed::<C::Inner>(rng))
+        .collect()
+}
+
+impl<T: GenerateRandom> GenerateRandom for Vec<T> {
+    fn generate_random<C: MtCfg>(rng: &mut impl Rng) -> Self {
+        generate_seq::<C, T, _>(rng)
+    }
+}
+
+impl<T: GenerateRandom> GenerateRandom for VecDeque<T> {
+    fn generate_random<C: MtCfg>(rng: &mut impl Rng) -> Self {
+        generate_seq::<C, T, _>(rng)
+    }
+}
+
+impl<T: GenerateRandom + Ord> GenerateRandom for BTreeSet<T> {
+    fn generate_random<C: MtCfg>(rng: &mut impl Rng) -> Self {
+        generate_seq::<C, T, _>(rng)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: GenerateRandom + Eq + Hash, S: BuildHasher + Default> GenerateRandom for HashSet<T, S> {
+    fn generate_random<C: MtCfg>(rng: &mut impl Rng) -> Self {
+        generate_seq::<C, T, _>(rng)
+    }
+}
+
+#[cfg(feature = "indexmap")]
+impl<T: GenerateRandom + Eq + Hash, S: BuildHasher + Default> GenerateRandom for IndexSet<T, S> {
+    fn generate_random<C: MtCfg>(rng: &mut impl Rng) -> Self {
+        generate_seq::<C, T, _>(rng)
+    }
+}
+
+impl<K: GenerateRandom + Ord, V: GenerateRandom> GenerateRandom for BTreeMap<K, V> {
+    fn generate_random<C: MtCfg>(rng: &mut impl Rng) -> Self {
+        generate_seq::<C, MapEntry<K, V>, _>(rng)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K, V, S> GenerateRandom for HashMap<K, V, S>
+where
+    K: GenerateRandom + Eq + Hash,
+    V: GenerateRandom,
+    S: BuildHasher + Default,
+{
+    fn generate_random<C: MtCfg>(rng: &mut impl Rng) -> Self {
+        generate_seq::<C, MapEntry<K, V>, _>(rng)
+    }
+}
+
+#[cfg(feature = "indexmap")]
+impl<K, V, S> GenerateRandom for IndexMap<K, V, S>
+where
+    K: GenerateRandom + Eq + Hash,
+    V: GenerateRandom,
+    S: BuildHasher + Default,
+{
+    fn generate_random<C: MtCfg>(rng: &mut impl Rng) -> Self {
+        generate_seq::<C, MapEntry<K, V>, _>(rng)
+    }
+}
+
+// mirrors ser_tuple, each element gets the Inner config of the one before it,
+// all but the last one are followed by the next
+macro_rules! impl_tuple {
+    (@let $method:ident $rng:ident $C:ty; $T:ident $x:ident) => {
+        let $x = $T::$method::<$C>($rng);
+    };
+    (@let $method:ident $rng:ident $C:ty; $T:ident $x:ident $($rest:tt)+) => {
+        let $x = $T::generate_random_followed::<$C>($rng);
+        impl_tuple!(@let $method $rng <$C as MtCfg>::Inner; $($rest)+);
+    };
+    ($($T:ident $x:ident),+) => {
+        impl<$($T: GenerateRandom),+> GenerateRandom for ($($T,)+) {
+            fn generate_random<C: MtCfg>(rng: &mut impl Rng) -> Self {
+                impl_tuple!(@let generate_random rng C; $($T $x)+);
+                ($($x,)+)
+            }
+
+            fn generate_random_followed<C: MtCfg>(rng: &mut impl Rng) -> Self {
+                impl_tuple!(@let generate_random_followed rng C; $($T $x)+);
+                ($($x,)+)
+            }
+        }
+    };
+}
+
+impl_tuple!(A a, B b);
+impl_tuple!(A a, B b, D d);
+impl_tuple!(A a, B b, D d, E e);
+impl_tuple!(A a, B b, D d, E e, F f);
+impl_tuple!(A a, B b, D d, E e, F f, G g);
+impl_tuple!(A a, B b, D d, E e, F f, G g, H h);
+impl_tuple!(A a, B b, D d, E e, F f, G g, H h, I i);
+impl_tuple!(A a, B b, D d, E e, F f, G g, H h, I i, J j);
+impl_tuple!(A a, B b, D d, E e, F f, G g, H h, I i, J j, K k);
+impl_tuple!(A a, B b, D d, E e, F f, G g, H h, I i, J j, K k, L l);
+impl_tuple!(A a, B b, D d, E e, F f, G g, H h, I i, J j, K k, L l, M m);
+
+impl GenerateRandom for String {
+    fn generate_random<C: MtCfg>(rng: &mut impl Rng) -> Self {
+        let len = rng.gen_range(0..=max_seq_len::<C>());
+        fit_string::<C>((0..len).map(|_| rng.gen::<char>()))
+    }
+}
+
+macro_rules! impl_ptr {
+    ($P:ident) => {
+        impl<T: GenerateRandom> GenerateRandom for $P<T> {
+            fn generate_random<C: MtCfg>(rng: &mut impl Rng) -> Self {
+                $P::new(T::generate_random::<C>(rng))
+            }
+
+            fn generate_random_followed<C: MtCfg>(rng: &mut impl Rng) -> Self {
+                $P::new(T::generate_random_followed::<C>(rng))
+            }
+        }
+
+        impl<T: GenerateRandom> GenerateRandom for $P<[T]> {
+            fn generate_random<C: MtCfg>(rng: &mut impl Rng) -> Self {
+                Vec::<T>::generate_random::<C>(rng).into()
+            }
+        }
+
+        impl GenerateRandom for $P<str> {
+            fn generate_random<C: MtCfg>(rng: &mut impl Rng) -> Self {
+                String::generate_random::<C>(rng).into()
+            }
+        }
+    };
+}
+
+impl_ptr!(Box);
+impl_ptr!(Rc);
+impl_ptr!(Arc);
+
+impl<T: ToOwned + ?Sized> GenerateRandom for Cow<'_, T>
+where
+    T::Owned: GenerateRandom,
+{
+    fn generate_random<C: MtCfg>(rng: &mut impl Rng) -> Self {
+        Cow::Owned(T::Owned::generate_random::<C>(rng))
+    }
+
+    fn generate_random_followed<C: MtCfg>(rng: &mut impl Rng) -> Self {
+        Cow::Owned(T::Owned::generate_random_followed::<C>(rng))
+    }
+}
+
+impl<T: GenerateRandom> GenerateRandom for RangeInclusive<T> {
+    fn generate_random<C: MtCfg>(rng: &mut impl Rng) -> Self {
+        let (start, end) = <(T, T)>::generate_random::<((), ())>(rng);
+        start..=end
+    }
+
+    fn generate_random_followed<C: MtCfg>(rng: &mut impl Rng) -> Self {
+        let (start, end) = <(T, T)>::generate_random_followed::<((), ())>(rng);
+        start..=end
+    }
+}
+
+// draws before mt_generate_bits gives up on from_bits accepting a random value
+const MAX_BITS_TRIES: usize = 1000;
+
+// values of #[mt(bits = N)] fields, drawn until from_bits accepts one. types that accept
+// (almost) nothing get from_bits(0), and a panic if not even that is valid
+#[doc(hidden)]
+pub fn mt_generate_bits<T: MtBits>(rng: &mut impl Rng, bits: u8) -> T {
+    for _ in 0..MAX_BITS_TRIES {
+        if let Ok(x) = T::from_bits(rng.gen_range(0..=max_bits(bits))) {
+            return x;
+        }
+    }
+
+    T::from_bits(0).unwrap_or_else(|e| {
+        panic!("from_bits rejected {MAX_BITS_TRIES} random values of {bits} bits and 0: {e}")
+    })
+}
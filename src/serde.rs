@@ -0,0 +1,874 @@
+// serde Serializer/Deserializer writing the same bytes as the MtSerialize impls, so types
+// deriving serde can go on the wire as well. lengths and strings use a SerdeCfg, per field
+// it can be changed with #[serde(with = "mt_ser::serde::len_u8")] and the other modules
+// below. like with MtCfg, such a setting only applies to the field itself, its elements
+// are encoded with the default again (u16 lengths, UTF-8, u8 enum discriminants)
+
+use super::{DefCfg, DeserializeCtx, DeserializeError, MtCfg, MtDeserialize, MtLen, MtSerialize};
+use super::{SerializeError, Utf16};
+use crate::io::{self, Read, Write};
+use alloc::{
+    boxed::Box,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{fmt, marker::PhantomData};
+use serde::{
+    de::{
+        self,
+        value::{StrDeserializer, U32Deserializer},
+        DeserializeSeed, Visitor,
+    },
+    ser::{self, Serialize},
+    Deserialize,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SerdeLen {
+    U8,
+    U16,
+    U32,
+    U64,
+    ToEnd, // no length, sequences and strings extend to the end of the input
+}
+
+// how enum variants are encoded, serde passes the index of a variant (not its
+// discriminant) and its name (snake_case with #[serde(rename_all = "snake_case")]).
+// the numeric reprs thus only match the derive for discriminants counting up from 0,
+// enums with explicit discriminants need Str or their own Serialize impl
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SerdeRepr {
+    U8,
+    U16,
+    U32,
+    Str,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SerdeCfg {
+    pub len: SerdeLen,
+    pub utf16: bool,
+    pub repr: SerdeRepr,
+}
+
+// same as DefCfg
+impl Default for SerdeCfg {
+    fn default() -> Self {
+        Self::new(SerdeLen::U16, false, SerdeRepr::U8)
+    }
+}
+
+impl SerdeCfg {
+    pub const fn new(len: SerdeLen, utf16: bool, repr: SerdeRepr) -> Self {
+        Self { len, utf16, repr }
+    }
+}
+
+// runs $e with $C set to the MtCfg matching a SerdeCfg
+macro_rules! with_cfg {
+    ($cfg:expr, $C:ident => $e:expr) => {
+        match ($cfg.len, $cfg.utf16) {
+            (SerdeLen::U8, false) => with_cfg!(@ u8, $C => $e),
+            (SerdeLen::U16, false) => with_cfg!(@ u16, $C => $e),
+            (SerdeLen::U32, false) => with_cfg!(@ u32, $C => $e),
+            (SerdeLen::U64, false) => with_cfg!(@ u64, $C => $e),
+            (SerdeLen::ToEnd, false) => with_cfg!(@ (), $C => $e),
+            (SerdeLen::U8, true) => with_cfg!(@ Utf16<u8>, $C => $e),
+            (SerdeLen::U16, true) => with_cfg!(@ Utf16<u16>, $C => $e),
+            (SerdeLen::U32, true) => with_cfg!(@ Utf16<u32>, $C => $e),
+            (SerdeLen::U64, true) => with_cfg!(@ Utf16<u64>, $C => $e),
+            (SerdeLen::ToEnd, true) => with_cfg!(@ Utf16<()>, $C => $e),
+        }
+    };
+    (@ $T:ty, $C:ident => $e:expr) => {{
+        type $C = $T;
+        $e
+    }};
+}
+
+macro_rules! cfg_modules {
+    ($($name:ident: $len:ident, $utf16:expr, $repr:ident;)*) => {
+        $(
+            pub mod $name {
+                use super::*;
+
+                pub const NAME: &str = concat!("$mt_ser::serde::", stringify!($name));
+                pub const CFG: SerdeCfg = SerdeCfg::new(SerdeLen::$len, $utf16, SerdeRepr::$repr);
+
+                pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+                where
+                    T: Serialize + ?Sized,
+                    S: ser::Serializer,
+                {
+                    serializer.serialize_newtype_struct(NAME, value)
+                }
+
+                pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+                where
+                    T: Deserialize<'de>,
+                    D: de::Deserializer<'de>,
+                {
+                    deserializer.deserialize_newtype_struct(NAME, HintVisitor(PhantomData))
+                }
+            }
+        )*
+
+        // other serializers see a newtype struct around the field
+        fn hint(name: &str) -> Option<SerdeCfg> {
+            $(
+                if name == $name::NAME {
+                    return Some($name::CFG);
+                }
+            )*
+            None
+        }
+    };
+}
+
+cfg_modules! {
+    len_u8: U8, false, U8;
+    len_u16: U16, false, U8;
+    len_u32: U32, false, U8;
+    len_u64: U64, false, U8;
+    len_to_end: ToEnd, false, U8;
+    utf16: U16, true, U8;
+    utf16_u32: U32, true, U8;
+    repr_u16: U16, false, U16;
+    repr_u32: U16, false, U32;
+    repr_str: U16, false, Str;
+}
+
+struct HintVisitor<T>(PhantomData<T>);
+
+impl<'de, T: Deserialize<'de>> Visitor<'de> for HintVisitor<T> {
+    type Value = T;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a newtype struct")
+    }
+
+    fn visit_newtype_struct<D: de::Deserializer<'de>>(self, d: D) -> Result<T, D::Error> {
+        T::deserialize(d)
+    }
+}
+
+impl ser::Error for SerializeError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        SerializeError::Other(msg.to_string())
+    }
+}
+
+impl de::Error for DeserializeError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        DeserializeError::Other(msg.to_string())
+    }
+}
+
+pub fn to_writer<T: Serialize + ?Sized>(
+    writer: &mut impl Write,
+    value: &T,
+) -> Result<(), SerializeError> {
+    to_writer_with(writer, value, SerdeCfg::default())
+}
+
+// cfg applies to the value itself, like C in mt_serialize::<C>
+pub fn to_writer_with<T: Serialize + ?Sized>(
+    writer: &mut impl Write,
+    value: &T,
+    cfg: SerdeCfg,
+) -> Result<(), SerializeError> {
+    value.serialize(&mut Serializer {
+        writer,
+        hint: Some(cfg),
+    })
+}
+
+pub fn to_vec<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, SerializeError> {
+    let mut writer = Vec::new();
+    to_writer(&mut writer, value)?;
+    Ok(writer)
+}
+
+pub fn from_reader<T: de::DeserializeOwned>(reader: &mut impl Read) -> Result<T, DeserializeError> {
+    from_reader_with(reader, SerdeCfg::default(), &mut DeserializeCtx::default())
+}
+
+// cfg like with to_writer_with, ctx supplies the limits
+pub fn from_reader_with<T: de::DeserializeOwned>(
+    reader: &mut impl Read,
+    cfg: SerdeCfg,
+    ctx: &mut DeserializeCtx,
+) -> Result<T, DeserializeError> {
+    T::deserialize(&mut Deserializer {
+        reader: PeekRead {
+            inner: reader,
+            peeked: None,
+        },
+        ctx,
+        hint: Some(cfg),
+    })
+}
+
+pub fn from_slice<T: de::DeserializeOwned>(mut bytes: &[u8]) -> Result<T, DeserializeError> {
+    from_reader(&mut bytes)
+}
+
+pub struct Serializer<'a, W> {
+    writer: &'a mut W,
+    hint: Option<SerdeCfg>,
+}
+
+impl<W: Write> Serializer<'_, W> {
+    // the setting for the current value, anything nested gets the default
+    fn cfg(&mut self) -> SerdeCfg {
+        self.hint.take().unwrap_or_default()
+    }
+
+    fn num(&mut self, v: impl MtSerialize) -> Result<(), SerializeError> {
+        self.cfg();
+        v.mt_serialize::<DefCfg>(self.writer)
+    }
+
+    fn len(&mut self, cfg: SerdeCfg, len: Option<usize>) -> Result<(), SerializeError> {
+        if cfg.len == SerdeLen::ToEnd {
+            return Ok(());
+        }
+
+        let len = len.ok_or_else(|| SerializeError::Other("unknown sequence length".into()))?;
+        with_cfg!(cfg, C => C::write_len(len, self.writer))
+    }
+
+    fn variant(&mut self, index: u32, name: &'static str) -> Result<(), SerializeError> {
+        match self.cfg().repr {
+            SerdeRepr::U8 => u8::try_from(index)?.mt_serialize::<DefCfg>(self.writer),
+            SerdeRepr::U16 => u16::try_from(index)?.mt_serialize::<DefCfg>(self.writer),
+            SerdeRepr::U32 => index.mt_serialize::<DefCfg>(self.writer),
+            SerdeRepr::Str => name.mt_serialize::<DefCfg>(self.writer),
+        }
+    }
+}
+
+impl<W: Write> ser::Serializer for &mut Serializer<'_, W> {
+    type Ok = ();
+    type Error = SerializeError;
+
+    type SerializeSeq = Self;
+    type SerializeTuple = Self;
+    type SerializeTupleStruct = Self;
+    type SerializeTupleVariant = Self;
+    type SerializeMap = Self;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Self;
+
+    fn serialize_bool(self, v: bool) -> Result<(), SerializeError> {
+        self.num(v)
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<(), SerializeError> {
+        self.num(v)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<(), SerializeError> {
+        self.num(v)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<(), SerializeError> {
+        self.num(v)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<(), SerializeError> {
+        self.num(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<(), SerializeError> {
+        self.num(v)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<(), SerializeError> {
+        self.num(v)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<(), SerializeError> {
+        self.num(v)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<(), SerializeError> {
+        self.num(v)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<(), SerializeError> {
+        self.num(v)
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<(), SerializeError> {
+        self.num(v)
+    }
+
+    fn serialize_char(self, _v: char) -> Result<(), SerializeError> {
+        Err(SerializeError::Other("char is not supported".into()))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), SerializeError> {
+        let cfg = self.cfg();
+        with_cfg!(cfg, C => v.mt_serialize::<C>(self.writer))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), SerializeError> {
+        let cfg = self.cfg();
+        with_cfg!(cfg, C => v.mt_serialize::<C>(self.writer))
+    }
+
+    // only at the end of the input, like Option<T>
+    fn serialize_none(self) -> Result<(), SerializeError> {
+        self.cfg();
+        Ok(())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), SerializeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), SerializeError> {
+        self.cfg();
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), SerializeError> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        index: u32,
+        variant: &'static str,
+    ) -> Result<(), SerializeError> {
+        self.variant(index, variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<(), SerializeError> {
+        if let Some(cfg) = hint(name) {
+            self.hint = Some(cfg);
+        }
+
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<(), SerializeError> {
+        self.variant(index, variant)?;
+        value.serialize(self)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self, SerializeError> {
+        let cfg = self.cfg();
+        self.len(cfg, len)?;
+        Ok(self)
+    }
+
+    // tuples and arrays have no length
+    fn serialize_tuple(self, _len: usize) -> Result<Self, SerializeError> {
+        self.cfg();
+        Ok(self)
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self, SerializeError> {
+        self.serialize_tuple(len)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self, SerializeError> {
+        self.variant(index, variant)?;
+        Ok(self)
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self, SerializeError> {
+        self.serialize_seq(len)
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<Self, SerializeError> {
+        self.serialize_tuple(len)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        name: &'static str,
+        index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self, SerializeError> {
+        self.serialize_tuple_variant(name, index, variant, len)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+macro_rules! impl_compound {
+    ($($Trait:ident $method:ident $(($key:ident))?;)*) => {
+        $(
+            impl<W: Write> ser::$Trait for &mut Serializer<'_, W> {
+                type Ok = ();
+                type Error = SerializeError;
+
+                fn $method<T: Serialize + ?Sized>(
+                    &mut self,
+                    $($key: &'static str,)?
+                    value: &T,
+                ) -> Result<(), SerializeError> {
+                    $(let _ = $key;)?
+                    value.serialize(&mut **self)
+                }
+
+                fn end(self) -> Result<(), SerializeError> {
+                    Ok(())
+                }
+            }
+        )*
+    };
+}
+
+impl_compound! {
+    SerializeSeq serialize_element;
+    SerializeTuple serialize_element;
+    SerializeTupleStruct serialize_field;
+    SerializeTupleVariant serialize_field;
+    SerializeStruct serialize_field (key);
+    SerializeStructVariant serialize_field (key);
+}
+
+impl<W: Write> ser::SerializeMap for &mut Serializer<'_, W> {
+    type Ok = ();
+    type Error = SerializeError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), SerializeError> {
+        key.serialize(&mut **self)
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerializeError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), SerializeError> {
+        Ok(())
+    }
+}
+
+// Option and sequences without length need to know whether there is more input
+struct PeekRead<R> {
+    inner: R,
+    peeked: Option<u8>,
+}
+
+impl<R: Read> PeekRead<R> {
+    fn at_end(&mut self) -> Result<bool, DeserializeError> {
+        if self.peeked.is_none() {
+            let mut buf = [0];
+            if self.inner.read(&mut buf)? == 0 {
+                return Ok(true);
+            }
+            self.peeked = Some(buf[0]);
+        }
+
+        Ok(false)
+    }
+}
+
+impl<R: Read> Read for PeekRead<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match (self.peeked.take(), buf.split_first_mut()) {
+            (Some(x), Some((first, _))) => {
+                *first = x;
+                Ok(1)
+            }
+            (peeked, _) => {
+                self.peeked = peeked;
+                self.inner.read(buf)
+            }
+        }
+    }
+}
+
+pub struct Deserializer<'a, 'c, R> {
+    reader: PeekRead<&'a mut R>,
+    ctx: &'a mut DeserializeCtx<'c>,
+    hint: Option<SerdeCfg>,
+}
+
+impl<R: Read> Deserializer<'_, '_, R> {
+    fn cfg(&mut self) -> SerdeCfg {
+        self.hint.take().unwrap_or_default()
+    }
+
+    fn num<T: MtDeserialize>(&mut self) -> Result<T, DeserializeError> {
+        self.cfg();
+        T::mt_deserialize_with::<DefCfg>(&mut self.reader, self.ctx)
+    }
+
+    // None until the end of the input
+    fn len(&mut self, cfg: SerdeCfg) -> Result<Option<usize>, DeserializeError> {
+        with_cfg!(cfg, C => Ok(C::read_len(&mut self.reader)?.option()))
+    }
+
+    fn variant(&mut self) -> Result<Variant, DeserializeError> {
+        let repr = self.cfg().repr;
+        let reader = &mut self.reader;
+
+        Ok(match repr {
+            SerdeRepr::U8 => Variant::Index(u8::mt_deserialize::<DefCfg>(reader)?.into()),
+            SerdeRepr::U16 => Variant::Index(u16::mt_deserialize::<DefCfg>(reader)?.into()),
+            SerdeRepr::U32 => Variant::Index(u32::mt_deserialize::<DefCfg>(reader)?),
+            SerdeRepr::Str => {
+                Variant::Name(String::mt_deserialize_with::<DefCfg>(reader, self.ctx)?)
+            }
+        })
+    }
+}
+
+impl<'de, R: Read> de::Deserializer<'de> for &mut Deserializer<'_, '_, R> {
+    type Error = DeserializeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, DeserializeError> {
+        Err(DeserializeError::Other(
+            "the format is not self-describing".into(),
+        ))
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeserializeError> {
+        visitor.visit_bool(self.num()?)
+    }
+
+    fn deserialize_i8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeserializeError> {
+        visitor.visit_i8(self.num()?)
+    }
+
+    fn deserialize_i16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeserializeError> {
+        visitor.visit_i16(self.num()?)
+    }
+
+    fn deserialize_i32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeserializeError> {
+        visitor.visit_i32(self.num()?)
+    }
+
+    fn deserialize_i64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeserializeError> {
+        visitor.visit_i64(self.num()?)
+    }
+
+    fn deserialize_u8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeserializeError> {
+        visitor.visit_u8(self.num()?)
+    }
+
+    fn deserialize_u16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeserializeError> {
+        visitor.visit_u16(self.num()?)
+    }
+
+    fn deserialize_u32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeserializeError> {
+        visitor.visit_u32(self.num()?)
+    }
+
+    fn deserialize_u64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeserializeError> {
+        visitor.visit_u64(self.num()?)
+    }
+
+    fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeserializeError> {
+        visitor.visit_f32(self.num()?)
+    }
+
+    fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeserializeError> {
+        visitor.visit_f64(self.num()?)
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, DeserializeError> {
+        Err(DeserializeError::Other("char is not supported".into()))
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeserializeError> {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeserializeError> {
+        let cfg = self.cfg();
+        let st = with_cfg!(cfg, C => String::mt_deserialize_with::<C>(&mut self.reader, self.ctx))?;
+        visitor.visit_string(st)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeserializeError> {
+        self.deserialize_byte_buf(visitor)
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(
+        self,
+        visitor: V,
+    ) -> Result<V::Value, DeserializeError> {
+        let cfg = self.cfg();
+        let bytes =
+            with_cfg!(cfg, C => Vec::<u8>::mt_deserialize_with::<C>(&mut self.reader, self.ctx))?;
+        visitor.visit_byte_buf(bytes)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeserializeError> {
+        if self.reader.at_end()? {
+            self.cfg();
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeserializeError> {
+        self.cfg();
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, DeserializeError> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, DeserializeError> {
+        if let Some(cfg) = hint(name) {
+            self.hint = Some(cfg);
+        }
+
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeserializeError> {
+        let cfg = self.cfg();
+        let len = self.len(cfg)?;
+
+        if let Some(x) = len {
+            self.ctx.limits.check_len(x)?;
+        }
+
+        visitor.visit_seq(SeqAccess { de: self, len })
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, DeserializeError> {
+        self.cfg();
+        visitor.visit_seq(SeqAccess {
+            de: self,
+            len: Some(len),
+        })
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, DeserializeError> {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeserializeError> {
+        let cfg = self.cfg();
+        let len = self.len(cfg)?;
+
+        if let Some(x) = len {
+            self.ctx.limits.check_len(x)?;
+        }
+
+        visitor.visit_map(SeqAccess { de: self, len })
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, DeserializeError> {
+        self.deserialize_tuple(fields.len(), visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, DeserializeError> {
+        let variant = match self.variant()? {
+            Variant::Index(i) if (i as usize) < variants.len() => Variant::Index(i),
+            Variant::Name(x) if variants.contains(&x.as_str()) => Variant::Name(x),
+            Variant::Index(i) => return Err(DeserializeError::InvalidEnum(name, Box::new(i))),
+            Variant::Name(x) => return Err(DeserializeError::InvalidEnum(name, Box::new(x))),
+        };
+
+        visitor.visit_enum(VariantAccess { de: self, variant })
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(
+        self,
+        visitor: V,
+    ) -> Result<V::Value, DeserializeError> {
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(
+        self,
+        visitor: V,
+    ) -> Result<V::Value, DeserializeError> {
+        self.deserialize_any(visitor)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+// elements of sequences, tuples and structs, or the entries of maps
+struct SeqAccess<'a, 'b, 'c, R> {
+    de: &'a mut Deserializer<'b, 'c, R>,
+    len: Option<usize>, // None until the end of the input
+}
+
+impl<R: Read> SeqAccess<'_, '_, '_, R> {
+    fn next(&mut self) -> Result<bool, DeserializeError> {
+        match &mut self.len {
+            Some(0) => Ok(false),
+            Some(x) => {
+                *x -= 1;
+                Ok(true)
+            }
+            None => Ok(!self.de.reader.at_end()?),
+        }
+    }
+}
+
+impl<'de, R: Read> de::SeqAccess<'de> for SeqAccess<'_, '_, '_, R> {
+    type Error = DeserializeError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, DeserializeError> {
+        if !self.next()? {
+            return Ok(None);
+        }
+
+        self.de.ctx.limits.alloc(core::mem::size_of::<T::Value>())?;
+        self.de.ctx.limits.enter()?;
+        let res = seed.deserialize(&mut *self.de);
+        self.de.ctx.limits.leave();
+
+        res.map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.len
+    }
+}
+
+impl<'de, R: Read> de::MapAccess<'de> for SeqAccess<'_, '_, '_, R> {
+    type Error = DeserializeError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, DeserializeError> {
+        if !self.next()? {
+            return Ok(None);
+        }
+
+        seed.deserialize(&mut *self.de).map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, DeserializeError> {
+        seed.deserialize(&mut *self.de)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.len
+    }
+}
+
+enum Variant {
+    Index(u32),
+    Name(String),
+}
+
+struct VariantAccess<'a, 'b, 'c, R> {
+    de: &'a mut Deserializer<'b, 'c, R>,
+    variant: Variant,
+}
+
+impl<'de, R: Read> de::EnumAccess<'de> for VariantAccess<'_, '_, '_, R> {
+    type Error = DeserializeError;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Self), DeserializeError> {
+        let value = match &self.variant {
+            Variant::Index(i) => seed.deserialize(U32Deserializer::<DeserializeError>::new(*i))?,
+            Variant::Name(x) => seed.deserialize(StrDeserializer::<DeserializeError>::new(x))?,
+        };
+
+        Ok((value, self))
+    }
+}
+
+impl<'de, R: Read> de::VariantAccess<'de> for VariantAccess<'_, '_, '_, R> {
+    type Error = DeserializeError;
+
+    fn unit_variant(self) -> Result<(), DeserializeError> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(
+        self,
+        seed: T,
+    ) -> Result<T::Value, DeserializeError> {
+        seed.deserialize(self.de)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(
+        self,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, DeserializeError> {
+        de::Deserializer::deserialize_tuple(self.de, len, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, DeserializeError> {
+        de::Deserializer::deserialize_tuple(self.de, fields.len(), visitor)
+    }
+}
@@ -0,0 +1,260 @@
+use super::*;
+use generate::{fit_string, max_bits, max_seq_len, MapEntry};
+use proptest::{collection::vec, prelude::*, strategy::LazyJust};
+
+// proptest strategies that only produce values mt_serialize::<C> accepts, i.e. sequences
+// and strings fit into the length prefix of C (recursive types are not supported,
+// the strategy of a type is built from those of its fields up front)
+pub trait MtStrategy: Sized + Debug + 'static {
+    fn mt_strategy<C: MtCfg>() -> BoxedStrategy<Self>;
+
+    // for values that more data follows. a None is only read back at the end of the input,
+    // so Option (and anything ending in one) is always Some here
+    fn mt_strategy_followed<C: MtCfg>() -> BoxedStrategy<Self> {
+        Self::mt_strategy::<C>()
+    }
+}
+
+// checks that item deserializes to an equal value and serializes to the same bytes again
+// (panics like assert_eq!, so it works both in plain tests and in proptest! bodies)
+pub fn assert_mt_roundtrip<C: MtCfg, T: MtSerialize + MtDeserialize + PartialEq + Debug>(item: &T) {
+    let mut bytes = Vec::new();
+    if let Err(e) = item.mt_serialize::<C>(&mut bytes) {
+        panic!("serializing {item:?} failed: {e}");
+    }
+
+    let mut reader = bytes.as_slice();
+    let des = match T::mt_deserialize::<C>(&mut reader) {
+        Ok(x) => x,
+        Err(e) => panic!("deserializing {item:?} from {bytes:02x?} failed: {e}"),
+    };
+    assert!(
+        reader.is_empty(),
+        "{} trailing bytes after {item:?}",
+        reader.len()
+    );
+    assert_eq!(*item, des);
+
+    let mut again = Vec::new();
+    des.mt_serialize::<C>(&mut again).unwrap();
+    assert_eq!(
+        bytes, again,
+        "{item:?} serializes differently after a roundtrip"
+    );
+}
+
+macro_rules! impl_any {
+    ($($T:ty)*) => {
+        $(
+            impl MtStrategy for $T {
+                fn mt_strategy<C: MtCfg>() -> BoxedStrategy<Self> {
+                    any::<$T>().boxed()
+                }
+            }
+        )*
+    };
+}
+
+impl_any!(u8 i8 u16 i16 u32 i32 f32 u64 i64 f64 bool);
+
+impl MtStrategy for () {
+    fn mt_strategy<C: MtCfg>() -> BoxedStrategy<Self> {
+        Just(()).boxed()
+    }
+}
+
+impl<T: MtStrategy, const N: usize> MtStrategy for [T; N] {
+    fn mt_strategy<C: MtCfg>() -> BoxedStrategy<Self> {
+        vec(T::mt_strategy::<DefCfg>(), N)
+            .prop_map(|x| x.try_into().unwrap())
+            .boxed()
+    }
+}
+
+impl<T, E> MtStrategy for EnumSet<E>
+where
+    T: MtStrategy,
+    E: EnumSetTypeWithRepr<Repr = T> + Debug + 'static,
+{
+    fn mt_strategy<C: MtCfg>() -> BoxedStrategy<Self> {
+        T::mt_strategy::<DefCfg>()
+            .prop_map(EnumSet::from_repr_truncated)
+            .boxed()
+    }
+}
+
+impl<T: MtStrategy> MtStrategy for Option<T> {
+    fn mt_strategy<C: MtCfg>() -> BoxedStrategy<Self> {
+        proptest::option::of(T::mt_strategy::<C>()).boxed()
+    }
+
+    fn mt_strategy_followed<C: MtCfg>() -> BoxedStrategy<Self> {
+        T::mt_strategy_followed::<C>().prop_map(Some).boxed()
+    }
+}
+
+macro_rules! impl_seq {
+    ($T:ident $(: $($bound:path),+)? $(; $S:ident)?) => {
+        impl<E $(, $S)?> MtStrategy for $T<E $(, $S)?>
+        where
+            E: MtStrategy $($(+ $bound)+)?,
+            $($S: BuildHasher + Default + 'static,)?
+        {
+            fn mt_strategy<C: MtCfg>() -> BoxedStrategy<Self> {
+                vec(E::mt_strategy_followed::<C::Inner>(), 0..=max_seq_len::<C>())
+                    .prop_map(|x| x.into_iter().collect())
+                    .boxed()
+            }
+        }
+    };
+}
+
+impl_seq!(Vec);
+impl_seq!(VecDeque);
+impl_seq!(BTreeSet: Ord);
+impl_seq!(HashSet: Eq, Hash; S);
+#[cfg(feature = "indexmap")]
+impl_seq!(IndexSet: Eq, Hash; S);
+
+macro_rules! impl_map {
+    ($T:ident: $($bound:path),+ $(; $S:ident)?) => {
+        impl<K, V $(, $S)?> MtStrategy for $T<K, V $(, $S)?>
+        where
+            K: MtStrategy $(+ $bound)+,
+            V: MtStrategy,
+            $($S: BuildHasher + Default + 'static,)?
+        {
+            fn mt_strategy<C: MtCfg>() -> BoxedStrategy<Self> {
+                vec(
+                    MapEntry::<K, V>::mt_strategy_followed::<C::Inner>(),
+                    0..=max_seq_len::<C>(),
+                )
+                    .prop_map(|x| x.into_iter().collect())
+                    .boxed()
+            }
+        }
+    };
+}
+
+impl_map!(BTreeMap: Ord);
+impl_map!(HashMap: Eq, Hash; S);
+#[cfg(feature = "indexmap")]
+impl_map!(IndexMap: Eq, Hash; S);
+
+// mirrors ser_tuple, each element gets the Inner config of the one before it,
+// all but the last one are followed by the next
+macro_rules! impl_tuple {
+    (@let $method:ident $C:ty; $T:ident $x:ident) => {
+        let $x = $T::$method::<$C>();
+    };
+    (@let $method:ident $C:ty; $T:ident $x:ident $($rest:tt)+) => {
+        let $x = $T::mt_strategy_followed::<$C>();
+        impl_tuple!(@let $method <$C as MtCfg>::Inner; $($rest)+);
+    };
+    ($($T:ident $x:ident),+) => {
+        impl<$($T: MtStrategy),+> MtStrategy for ($($T,)+) {
+            fn mt_strategy<C: MtCfg>() -> BoxedStrategy<Self> {
+                impl_tuple!(@let mt_strategy C; $($T $x)+);
+                ($($x,)+).boxed()
+            }
+
+            fn mt_strategy_followed<C: MtCfg>() -> BoxedStrategy<Self> {
+                impl_tuple!(@let mt_strategy_followed C; $($T $x)+);
+                ($($x,)+).boxed()
+            }
+        }
+    };
+}
+
+impl_tuple!(A a, B b);
+impl_tuple!(A a, B b, D d);
+impl_tuple!(A a, B b, D d, E e);
+impl_tuple!(A a, B b, D d, E e, F f);
+impl_tuple!(A a, B b, D d, E e, F f, G g);
+impl_tuple!(A a, B b, D d, E e, F f, G g, H h);
+impl_tuple!(A a, B b, D d, E e, F f, G g, H h, I i);
+impl_tuple!(A a, B b, D d, E e, F f, G g, H h, I i, J j);
+impl_tuple!(A a, B b, D d, E e, F f, G g, H h, I i, J j, K k);
+impl_tuple!(A a, B b, D d, E e, F f, G g, H h, I i, J j, K k, L l);
+impl_tuple!(A a, B b, D d, E e, F f, G g, H h, I i, J j, K k, L l, M m);
+
+impl MtStrategy for String {
+    fn mt_strategy<C: MtCfg>() -> BoxedStrategy<Self> {
+        vec(any::<char>(), 0..=max_seq_len::<C>())
+            .prop_map(|chars| fit_string::<C>(chars))
+            .boxed()
+    }
+}
+
+macro_rules! impl_ptr {
+    ($P:ident) => {
+        impl<T: MtStrategy> MtStrategy for $P<T> {
+            fn mt_strategy<C: MtCfg>() -> BoxedStrategy<Self> {
+                T::mt_strategy::<C>().prop_map($P::new).boxed()
+            }
+
+            fn mt_strategy_followed<C: MtCfg>() -> BoxedStrategy<Self> {
+                T::mt_strategy_followed::<C>().prop_map($P::new).boxed()
+            }
+        }
+
+        impl<T: MtStrategy> MtStrategy for $P<[T]> {
+            fn mt_strategy<C: MtCfg>() -> BoxedStrategy<Self> {
+                Vec::<T>::mt_strategy::<C>().prop_map(Into::into).boxed()
+            }
+        }
+
+        impl MtStrategy for $P<str> {
+            fn mt_strategy<C: MtCfg>() -> BoxedStrategy<Self> {
+                String::mt_strategy::<C>().prop_map(Into::into).boxed()
+            }
+        }
+    };
+}
+
+impl_ptr!(Box);
+impl_ptr!(Rc);
+impl_ptr!(Arc);
+
+impl<T: ToOwned + Debug + ?Sized + 'static> MtStrategy for Cow<'static, T>
+where
+    T::Owned: MtStrategy,
+{
+    fn mt_strategy<C: MtCfg>() -> BoxedStrategy<Self> {
+        T::Owned::mt_strategy::<C>().prop_map(Cow::Owned).boxed()
+    }
+
+    fn mt_strategy_followed<C: MtCfg>() -> BoxedStrategy<Self> {
+        T::Owned::mt_strategy_followed::<C>()
+            .prop_map(Cow::Owned)
+            .boxed()
+    }
+}
+
+impl<T: MtStrategy> MtStrategy for RangeInclusive<T> {
+    fn mt_strategy<C: MtCfg>() -> BoxedStrategy<Self> {
+        <(T, T)>::mt_strategy::<((), ())>()
+            .prop_map(|(start, end)| start..=end)
+            .boxed()
+    }
+
+    fn mt_strategy_followed<C: MtCfg>() -> BoxedStrategy<Self> {
+        <(T, T)>::mt_strategy_followed::<((), ())>()
+            .prop_map(|(start, end)| start..=end)
+            .boxed()
+    }
+}
+
+// fields the derive can not generate values for, see derive_strategy
+#[doc(hidden)]
+pub fn mt_strategy_default<T: Default + Debug + 'static>() -> BoxedStrategy<T> {
+    LazyJust::new(T::default).boxed()
+}
+
+// values of #[mt(bits = N)] fields, those from_bits rejects are skipped
+#[doc(hidden)]
+pub fn mt_strategy_bits<T: MtBits + Debug + 'static>(bits: u8) -> BoxedStrategy<T> {
+    (0..=max_bits(bits))
+        .prop_filter_map("rejected by from_bits", |x| T::from_bits(x).ok())
+        .boxed()
+}
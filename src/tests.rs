@@ -28,3 +28,1713 @@ fn test_reserialize() {
         Err(SerializeError::TooBig(_))
     ));
 }
+
+#[test]
+fn test_tuples() {
+    let records = vec![
+        (1u16, String::from("a"), 0.5f32, true),
+        (2, "bc".into(), 1.5, false),
+    ];
+    assert_eq!(records, reserialize::<DefCfg, _>(&records));
+
+    // u8 length, then u32 length, then no length at all
+    type Cfg = (u8, (u32, ()));
+    let item = (vec![1u8], vec![2u8], vec![3u8, 4]);
+    assert_eq!(item.mt_serialized_len::<Cfg>().unwrap(), Some(2 + 5 + 2));
+    assert_eq!(item, reserialize::<Cfg, _>(&item));
+
+    let big = (
+        1u8,
+        2u16,
+        3u32,
+        4u64,
+        5i8,
+        6i16,
+        7i32,
+        8i64,
+        9f32,
+        10f64,
+        (),
+        false,
+    );
+    assert_eq!(big, reserialize::<DefCfg, _>(&big));
+}
+
+#[test]
+fn test_collections() {
+    use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+    use std::hash::{BuildHasherDefault, DefaultHasher};
+
+    let map: BTreeMap<u16, String> = [(2, "b".into()), (1, "a".into())].into();
+    let mut bytes = Vec::new();
+    map.mt_serialize::<DefCfg>(&mut bytes).unwrap();
+    // sorted by key
+    assert_eq!(bytes, [0, 2, 0, 1, 0, 1, b'a', 0, 2, 0, 1, b'b']);
+    assert_eq!(map, reserialize::<DefCfg, _>(&map));
+
+    let set: BTreeSet<u8> = [3, 1, 2].into();
+    assert_eq!(set, reserialize::<u8, _>(&set));
+
+    let queue: VecDeque<u32> = [1, 2, 3].into();
+    assert_eq!(queue, reserialize::<u32, _>(&queue));
+
+    let mut hashed: HashMap<u8, u8, BuildHasherDefault<DefaultHasher>> = HashMap::default();
+    hashed.insert(1, 2);
+    assert_eq!(hashed, reserialize::<DefCfg, _>(&hashed));
+
+    #[cfg(feature = "indexmap")]
+    {
+        let map: indexmap::IndexMap<u8, u16> = [(3, 1), (1, 2), (2, 3)].into_iter().collect();
+        let des = reserialize::<DefCfg, _>(&map);
+        assert!(des.keys().eq(map.keys()));
+    }
+}
+
+#[test]
+fn test_pointers() {
+    use std::{borrow::Cow, rc::Rc, sync::Arc};
+
+    fn bytes<C: MtCfg, T: MtSerialize + ?Sized>(item: &T) -> Vec<u8> {
+        let mut writer = Vec::new();
+        item.mt_serialize::<C>(&mut writer).unwrap();
+        writer
+    }
+
+    let name: Arc<str> = "µ".into();
+    assert_eq!(bytes::<DefCfg, _>("µ"), bytes::<DefCfg, _>(&name));
+    assert_eq!(name, reserialize::<DefCfg, _>(&name));
+    assert_eq!(name, reserialize::<Utf16<u8>, _>(&name));
+    assert_eq!(bytes::<Utf16<u8>, str>("µ"), [1, 0, 0xB5]);
+
+    let slice: &[u16] = &[1, 2];
+    assert_eq!(bytes::<u8, _>(slice), [2, 0, 1, 0, 2]);
+
+    let boxed: Box<[u16]> = slice.into();
+    assert_eq!(boxed, reserialize::<u8, _>(&boxed));
+    let boxed: Box<str> = "name".into();
+    assert_eq!(boxed, reserialize::<DefCfg, _>(&boxed));
+
+    let shared: Arc<[u16]> = slice.into();
+    assert_eq!(shared, reserialize::<(), _>(&shared));
+    let rc = Rc::new(Inner { len: vec![1] });
+    assert_eq!(rc, reserialize::<DefCfg, _>(&rc));
+
+    let cow: Cow<str> = Cow::Borrowed("cow");
+    assert_eq!(cow, reserialize::<DefCfg, _>(&cow));
+}
+
+#[derive(Debug, PartialEq, MtSerialize, MtDeserialize, MtDeserializeBorrowed)]
+struct Node {
+    id: u8,
+    #[mt(len = "u8")]
+    children: Vec<Node>,
+}
+
+#[test]
+fn test_limits() {
+    fn limited<C: MtCfg, T: MtDeserialize>(
+        bytes: &[u8],
+        limits: &mut Limits,
+    ) -> Result<T, DeserializeError> {
+        T::mt_deserialize_limited::<C>(&mut std::io::Cursor::new(bytes), limits)
+    }
+
+    fn exceeded<T: std::fmt::Debug>(res: Result<T, DeserializeError>) -> Limit {
+        match res.unwrap_err().inner() {
+            DeserializeError::LimitExceeded(x) => *x,
+            e => panic!("unexpected error: {e}"),
+        }
+    }
+
+    // hostile length prefix, rejected before reading any elements
+    let hostile = [0xFF, 0xFF, 0xFF, 0xFF, 1, 2, 3];
+    let mut limits = Limits::new(1024, usize::MAX, usize::MAX);
+    assert_eq!(
+        exceeded(limited::<u32, Vec<u8>>(&hostile, &mut limits)),
+        Limit::Len
+    );
+    assert_eq!(
+        exceeded(limited::<u32, String>(&hostile, &mut limits)),
+        Limit::Len
+    );
+
+    // variable length sequences are counted as they are read
+    let mut limits = Limits::new(2, usize::MAX, usize::MAX);
+    assert_eq!(
+        exceeded(limited::<(), Vec<u8>>(&[1, 2, 3], &mut limits)),
+        Limit::Len
+    );
+
+    let mut limits = Limits::new(usize::MAX, 4, usize::MAX);
+    assert_eq!(
+        limited::<u16, Vec<u16>>(&[0, 2, 0, 1, 0, 2], &mut limits).unwrap(),
+        [1, 2]
+    );
+    assert_eq!(limits.bytes(), 4);
+    assert_eq!(
+        exceeded(limited::<u8, String>(&[1, b'a'], &mut limits)),
+        Limit::Bytes
+    );
+
+    // four sequences deep
+    let mut deep = Node {
+        id: 0,
+        children: vec![],
+    };
+    for id in 1..5 {
+        deep = Node {
+            id,
+            children: vec![deep],
+        };
+    }
+    let mut bytes = Vec::new();
+    deep.mt_serialize::<DefCfg>(&mut bytes).unwrap();
+
+    let mut limits = Limits::new(usize::MAX, usize::MAX, 4);
+    assert_eq!(deep, limited::<DefCfg, Node>(&bytes, &mut limits).unwrap());
+
+    let mut limits = Limits::new(usize::MAX, usize::MAX, 3);
+    let err = limited::<DefCfg, Node>(&bytes, &mut limits).unwrap_err();
+    assert!(err
+        .to_string()
+        .starts_with("Node.children[0].children[0].children[0].children[0]:"));
+    assert!(matches!(
+        err.inner(),
+        DeserializeError::LimitExceeded(Limit::Depth)
+    ));
+
+    // unlimited by default
+    assert_eq!(deep, reserialize::<DefCfg, _>(&deep));
+
+    // borrowed deserialization applies the same limits
+    let borrowed = |bytes: &[u8], limits| {
+        let mut ctx = DeserializeCtx {
+            limits,
+            ..Default::default()
+        };
+        let mut reader = bytes;
+        Node::mt_deserialize_borrowed_with::<DefCfg>(&mut reader, &mut ctx)
+    };
+    assert_eq!(
+        exceeded(borrowed(
+            &[0, 0xFF, 1],
+            Limits::new(16, usize::MAX, usize::MAX)
+        )),
+        Limit::Len
+    );
+    assert_eq!(
+        exceeded(borrowed(&bytes, Limits::new(usize::MAX, usize::MAX, 3))),
+        Limit::Depth
+    );
+    assert_eq!(
+        deep,
+        borrowed(&bytes, Limits::new(usize::MAX, usize::MAX, 4)).unwrap()
+    );
+
+    let mut ctx = DeserializeCtx {
+        limits: Limits::new(1024, usize::MAX, usize::MAX),
+        ..Default::default()
+    };
+    assert_eq!(
+        exceeded(<&[u8]>::mt_deserialize_borrowed_with::<u32>(
+            &mut hostile.as_slice(),
+            &mut ctx
+        )),
+        Limit::Len
+    );
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn test_async_limits() {
+    async fn limited<C: MtCfg, T: MtAsyncDeserialize>(
+        bytes: &[u8],
+        limits: Limits,
+    ) -> Result<T, DeserializeError> {
+        let mut ctx = DeserializeCtx {
+            limits,
+            ..Default::default()
+        };
+        mt_deserialize_async_with::<T, C, _>(&mut &bytes[..], &mut ctx).await
+    }
+
+    fn exceeded<T: std::fmt::Debug>(res: Result<T, DeserializeError>) -> Limit {
+        match res.unwrap_err().inner() {
+            DeserializeError::LimitExceeded(x) => *x,
+            e => panic!("unexpected error: {e}"),
+        }
+    }
+
+    let hostile = [0xFF, 0xFF, 0xFF, 0xFF, 1, 2, 3];
+    let limits = || Limits::new(16, usize::MAX, usize::MAX);
+    assert_eq!(
+        exceeded(limited::<u32, Vec<u8>>(&hostile, limits()).await),
+        Limit::Len
+    );
+    assert_eq!(
+        exceeded(limited::<u32, String>(&hostile, limits()).await),
+        Limit::Len
+    );
+    assert_eq!(
+        exceeded(limited::<DefCfg, Outer>(&[0, 0, 0, 0, 0, 0, 0, 1, 0xFF], limits()).await),
+        Limit::Len
+    );
+
+    let deep = vec![vec![1u8]];
+    let mut bytes = Vec::new();
+    deep.mt_serialize::<DefCfg>(&mut bytes).unwrap();
+    assert_eq!(
+        exceeded(
+            limited::<DefCfg, Vec<Vec<u8>>>(&bytes, Limits::new(usize::MAX, usize::MAX, 1)).await
+        ),
+        Limit::Depth
+    );
+    assert_eq!(
+        deep,
+        limited::<DefCfg, Vec<Vec<u8>>>(&bytes, Limits::new(usize::MAX, usize::MAX, 2))
+            .await
+            .unwrap()
+    );
+}
+
+#[derive(Debug, PartialEq, MtSerialize, MtDeserialize, MtDeserializeBorrowed)]
+#[cfg_attr(feature = "async", derive(MtAsyncSerialize, MtAsyncDeserialize))]
+struct Bomb {
+    #[mt(size = "u32", zlib, max_decompressed = 64)]
+    capped: Vec<u8>,
+    #[mt(zstd)]
+    global: Vec<u8>,
+}
+
+#[test]
+fn test_max_decompressed() {
+    let bytes = |capped, global| {
+        let mut writer = Vec::new();
+        Bomb {
+            capped: vec![0; capped],
+            global: vec![0; global],
+        }
+        .mt_serialize::<DefCfg>(&mut writer)
+        .unwrap();
+        writer
+    };
+
+    // 2 byte length plus 62 bytes is exactly at the limit
+    let ok = bytes(62, 1000);
+    let des = Bomb::mt_deserialize::<DefCfg>(&mut std::io::Cursor::new(&ok)).unwrap();
+    assert_eq!(des.capped.len(), 62);
+
+    let err = Bomb::mt_deserialize::<DefCfg>(&mut std::io::Cursor::new(bytes(63, 0))).unwrap_err();
+    assert_eq!(err.path().unwrap().to_string(), "Bomb.capped[62]");
+    assert!(matches!(
+        err.inner(),
+        DeserializeError::LimitExceeded(Limit::Decompressed)
+    ));
+    assert!(err.to_string().ends_with("Decompressed limit exceeded"));
+
+    let mut limits = Limits::default();
+    limits.max_decompressed = 500;
+    let err = Bomb::mt_deserialize_limited::<DefCfg>(&mut std::io::Cursor::new(&ok), &mut limits)
+        .unwrap_err();
+    assert_eq!(err.path().unwrap().to_string(), "Bomb.global[498]");
+
+    let mut ctx = DeserializeCtx {
+        limits,
+        ..Default::default()
+    };
+    let err =
+        Bomb::mt_deserialize_borrowed_with::<DefCfg>(&mut ok.as_slice(), &mut ctx).unwrap_err();
+    assert_eq!(err.path().unwrap().to_string(), "Bomb.global[498]");
+}
+
+// same attributes as mt_derive(enumset) emits
+#[derive(Debug, enumset::EnumSetType)]
+#[enumset(serialize_repr = "map")]
+#[enumset(repr = "u8")]
+enum Flag {
+    A,
+    B,
+    C,
+}
+
+#[test]
+fn test_fixed() {
+    let arr = [[1u16, 2], [3, 4], [5, 6]];
+    assert_eq!(arr, reserialize::<DefCfg, _>(&arr));
+
+    let err =
+        <[u16; 3]>::mt_deserialize::<DefCfg>(&mut std::io::Cursor::new([0, 1, 0, 2])).unwrap_err();
+    assert_eq!(err.path().unwrap().to_string(), "[2]");
+
+    let flags = Flag::A | Flag::C;
+    assert_eq!(flags, reserialize::<DefCfg, _>(&flags));
+
+    // unpaired surrogate
+    let bytes = [0, 1, 0xD8, 0];
+    assert!(matches!(
+        String::mt_deserialize::<Utf16>(&mut std::io::Cursor::new(bytes)),
+        Err(DeserializeError::InvalidUtf16(_))
+    ));
+}
+
+#[derive(Debug, PartialEq, MtSerialize, MtDeserialize)]
+#[cfg_attr(feature = "async", derive(MtAsyncSerialize, MtAsyncDeserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "proptest", derive(MtStrategy))]
+#[cfg_attr(feature = "random", derive(GenerateRandom))]
+struct Inner {
+    #[mt(len = "u8")]
+    len: Vec<u8>,
+}
+
+#[derive(Debug, PartialEq, MtSerialize, MtDeserialize)]
+#[cfg_attr(feature = "async", derive(MtAsyncSerialize, MtAsyncDeserialize))]
+#[cfg_attr(feature = "proptest", derive(MtStrategy))]
+#[cfg_attr(feature = "random", derive(GenerateRandom))]
+struct Outer {
+    id: u32,
+    #[mt(len = "u32")]
+    style: Vec<Inner>,
+}
+
+#[derive(Debug, PartialEq, MtSerialize, MtDeserialize)]
+#[cfg_attr(feature = "async", derive(MtAsyncSerialize, MtAsyncDeserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "proptest", derive(MtStrategy))]
+#[cfg_attr(feature = "random", derive(GenerateRandom))]
+struct Compressed {
+    #[mt(size = "u32", zlib)]
+    a: Inner,
+    #[mt(size = "u32", zstd)]
+    b: Inner,
+    c: u8,
+}
+
+#[test]
+fn test_compressed_fields() {
+    let item = Compressed {
+        a: Inner { len: vec![1, 2] },
+        b: Inner { len: vec![3] },
+        c: 4,
+    };
+    assert_eq!(item, reserialize::<DefCfg, _>(&item));
+}
+
+#[derive(Debug, PartialEq, MtSerialize, MtDeserialize)]
+#[cfg_attr(feature = "async", derive(MtAsyncSerialize, MtAsyncDeserialize))]
+struct Levels {
+    #[mt(size = "u32", zlib(level = 9))]
+    fast: Vec<u8>,
+    #[mt(size = "u32", zlib(level = 0))]
+    stored: Vec<u8>,
+    // beyond the default maximum window of the decoder
+    #[mt(zstd(level = 3, window_log = 28))]
+    wide: Vec<u8>,
+}
+
+#[test]
+fn test_compression_level() {
+    let data: Vec<u8> = (0..4096u32).map(|i| (i * i / 7 % 13) as u8).collect();
+    let item = Levels {
+        fast: data.clone(),
+        stored: data.clone(),
+        wide: data,
+    };
+    assert_eq!(item, reserialize::<DefCfg, _>(&item));
+
+    // the level 0 section is stored and only grows
+    let size = |bytes: &[u8], at: usize| u32::from_be_bytes(bytes[at..at + 4].try_into().unwrap());
+    let mut bytes = Vec::new();
+    item.mt_serialize::<DefCfg>(&mut bytes).unwrap();
+    let fast = size(&bytes, 0) as usize;
+    assert!(fast < 4096);
+    assert!(size(&bytes, 4 + fast) > 4096);
+
+    // the context overrides the level of every zlib field
+    let mut ctx = SerializeCtx {
+        zlib_level: Some(0),
+        ..Default::default()
+    };
+    let mut overridden = Vec::new();
+    item.mt_serialize_with::<DefCfg>(&mut overridden, &mut ctx)
+        .unwrap();
+    assert!(size(&overridden, 0) > 4096);
+    assert_eq!(
+        item,
+        Levels::mt_deserialize::<DefCfg>(&mut std::io::Cursor::new(overridden)).unwrap()
+    );
+
+    // levels above 9 are clamped instead of reaching flate2
+    let at_level = |zlib_level| {
+        let mut ctx = SerializeCtx {
+            zlib_level: Some(zlib_level),
+            ..Default::default()
+        };
+        let mut bytes = Vec::new();
+        item.mt_serialize_with::<DefCfg>(&mut bytes, &mut ctx)
+            .unwrap();
+        bytes
+    };
+    assert_eq!(at_level(10), at_level(9));
+    assert_eq!(at_level(u32::MAX), at_level(9));
+}
+
+#[derive(Debug, PartialEq, MtSerialize, MtDeserialize)]
+#[cfg_attr(feature = "async", derive(MtAsyncSerialize, MtAsyncDeserialize))]
+struct Block {
+    #[mt(size = "u32", zlib(until = 29), zstd(since = 29))]
+    nodes: Vec<u16>,
+}
+
+#[test]
+fn test_versioned_codec() {
+    let item = Block {
+        nodes: vec![1, 2, 3],
+    };
+
+    let bytes = |version| {
+        let mut ctx = SerializeCtx {
+            version,
+            ..Default::default()
+        };
+        let mut writer = Vec::new();
+        item.mt_serialize_with::<DefCfg>(&mut writer, &mut ctx)
+            .unwrap();
+        writer
+    };
+
+    let des = |bytes: &[u8], version| {
+        let mut ctx = DeserializeCtx {
+            version,
+            ..Default::default()
+        };
+        Block::mt_deserialize_with::<DefCfg>(&mut std::io::Cursor::new(bytes), &mut ctx)
+    };
+
+    // zlib header, zstd magic number
+    let old = bytes(Some(28));
+    assert_eq!(old[4], 0x78);
+    assert_eq!(old[4..8], bytes(Some(1))[4..8]);
+    let new = bytes(Some(29));
+    assert_eq!(new[4..8], [0x28, 0xB5, 0x2F, 0xFD]);
+
+    assert_eq!(item, des(&old, Some(28)).unwrap());
+    assert_eq!(item, des(&new, Some(40)).unwrap());
+    assert!(des(&old, Some(29)).is_err());
+
+    // no version is the newest
+    assert_eq!(new, bytes(None));
+    assert_eq!(item, des(&new, None).unwrap());
+}
+
+#[derive(Debug, Clone, PartialEq, MtSerialize, MtDeserialize, MtDeserializeBorrowed)]
+#[cfg_attr(feature = "async", derive(MtAsyncSerialize, MtAsyncDeserialize))]
+#[cfg_attr(feature = "proptest", derive(MtStrategy))]
+#[cfg_attr(feature = "random", derive(GenerateRandom))]
+struct Hello {
+    id: u8,
+    #[mt(since = 37)]
+    name: String,
+    #[mt(until = 40)]
+    legacy: u8,
+}
+
+#[test]
+fn test_versioned_fields() {
+    let item = Hello {
+        id: 1,
+        name: "a".into(),
+        legacy: 2,
+    };
+
+    let reserialize = |version| {
+        let mut writer = Vec::new();
+        let mut ctx = SerializeCtx {
+            version,
+            ..Default::default()
+        };
+        item.mt_serialize_with::<DefCfg>(&mut writer, &mut ctx)
+            .unwrap();
+
+        let mut ctx = DeserializeCtx {
+            version,
+            ..Default::default()
+        };
+        let des =
+            Hello::mt_deserialize_with::<DefCfg>(&mut std::io::Cursor::new(&writer), &mut ctx)
+                .unwrap();
+        (writer, des)
+    };
+
+    assert_eq!(
+        reserialize(Some(36)),
+        (
+            vec![1, 2],
+            Hello {
+                name: String::new(),
+                ..item.clone()
+            }
+        )
+    );
+    assert_eq!(
+        reserialize(Some(37)),
+        (vec![1, 0, 1, b'a', 2], item.clone())
+    );
+    assert_eq!(
+        reserialize(Some(40)),
+        (
+            vec![1, 0, 1, b'a'],
+            Hello {
+                legacy: 0,
+                ..item.clone()
+            }
+        )
+    );
+
+    // without a context borrowed deserialization reads the newest version
+    assert_eq!(
+        Hello::mt_deserialize_borrowed::<DefCfg>(&mut [1, 0, 1, b'a'].as_slice()).unwrap(),
+        reserialize(None).1
+    );
+}
+
+#[derive(Debug, PartialEq, MtSerialize, MtDeserialize, MtDeserializeBorrowed)]
+#[cfg_attr(feature = "async", derive(MtAsyncSerialize, MtAsyncDeserialize))]
+#[mt(version = "u8", min_version = 2, max_version = 4)]
+#[cfg_attr(feature = "proptest", derive(MtStrategy))]
+#[cfg_attr(feature = "random", derive(GenerateRandom))]
+struct ToolCaps {
+    interval: u16,
+    #[mt(min_version = 3)]
+    damage: u16,
+    #[mt(max_version = 3)]
+    legacy: u8,
+}
+
+#[test]
+fn test_version_byte() {
+    let des = |bytes: &[u8]| ToolCaps::mt_deserialize::<DefCfg>(&mut std::io::Cursor::new(bytes));
+
+    // without a context version the newest supported version is written,
+    // without the fields it removed
+    let caps = ToolCaps {
+        interval: 1,
+        damage: 2,
+        legacy: 0,
+    };
+    let mut bytes = Vec::new();
+    caps.mt_serialize::<DefCfg>(&mut bytes).unwrap();
+    assert_eq!(bytes, [4, 0, 1, 0, 2]);
+    assert_eq!(caps, des(&bytes).unwrap());
+
+    // older peers get an older version of the struct, independent of the protocol version
+    let old = ToolCaps {
+        interval: 1,
+        damage: 0,
+        legacy: 3,
+    };
+    let at = |version| {
+        let mut ctx = SerializeCtx {
+            version: Some(40),
+            struct_versions: [("ToolCaps", version)].into(),
+            ..Default::default()
+        };
+        let mut bytes = Vec::new();
+        old.mt_serialize_with::<DefCfg>(&mut bytes, &mut ctx)
+            .map(|_| bytes)
+    };
+    assert_eq!(at(2).unwrap(), [2, 0, 1, 3]);
+    assert_eq!(old, des(&at(2).unwrap()).unwrap());
+    assert_eq!(at(3).unwrap(), [3, 0, 1, 0, 0, 3]);
+    assert_eq!(at(4).unwrap(), [4, 0, 1, 0, 0]);
+    assert_eq!(
+        old.mt_serialized_len_with::<DefCfg>(&mut SerializeCtx {
+            struct_versions: [("ToolCaps", 2)].into(),
+            ..Default::default()
+        })
+        .unwrap(),
+        Some(4)
+    );
+
+    // the protocol version alone does not select a struct version
+    let mut ctx = SerializeCtx {
+        version: Some(2),
+        ..Default::default()
+    };
+    let mut bytes = Vec::new();
+    old.mt_serialize_with::<DefCfg>(&mut bytes, &mut ctx)
+        .unwrap();
+    assert_eq!(bytes[0], 4);
+
+    for version in [1, 5] {
+        let err = at(version).unwrap_err();
+        assert!(matches!(
+            err,
+            SerializeError::UnsupportedVersion("ToolCaps", x) if x == version
+        ));
+    }
+
+    assert_eq!(
+        des(&[3, 0, 1, 0, 2, 3]).unwrap(),
+        ToolCaps {
+            interval: 1,
+            damage: 2,
+            legacy: 3,
+        }
+    );
+    assert_eq!(
+        ToolCaps::mt_deserialize_borrowed::<DefCfg>(&mut [2, 0, 1, 3].as_slice()).unwrap(),
+        ToolCaps {
+            interval: 1,
+            damage: 0,
+            legacy: 3,
+        }
+    );
+
+    for version in [1, 5] {
+        let err = des(&[version, 0, 1, 0, 2]).unwrap_err();
+        assert!(matches!(
+            err,
+            DeserializeError::UnsupportedVersion("ToolCaps", x) if x == version as u64
+        ));
+    }
+}
+
+// name-id table built up while serializing, like the one sent with a mapblock
+#[derive(Default)]
+struct NodeIds(Vec<String>);
+
+impl NodeIds {
+    fn id(&mut self, name: &str) -> u16 {
+        match self.0.iter().position(|x| x == name) {
+            Some(i) => i as u16,
+            None => {
+                self.0.push(name.into());
+                self.0.len() as u16 - 1
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, MtSerialize, MtDeserialize)]
+#[cfg_attr(feature = "async", derive(MtAsyncSerialize, MtAsyncDeserialize))]
+struct Nodes {
+    #[mt(
+        context = "NodeIds",
+        map_ser = "|names: &Vec<String>, ids: &mut NodeIds| Ok(names.iter().map(|x| ids.id(x)).collect::<Vec<_>>())",
+        map_des = "|raw: Vec<u16>, ids: &mut NodeIds| raw.into_iter().map(|x| ids.0.get(x as usize).cloned().ok_or(DeserializeError::Other(\"unknown node\".into()))).collect()"
+    )]
+    names: Vec<String>,
+}
+
+#[test]
+fn test_user_context() {
+    let nodes = Nodes {
+        names: vec!["air".into(), "stone".into(), "air".into()],
+    };
+
+    let mut ids = NodeIds::default();
+    let mut ctx = SerializeCtx {
+        user: Some(&mut ids),
+        ..Default::default()
+    };
+    let mut bytes = Vec::new();
+    nodes
+        .mt_serialize_with::<DefCfg>(&mut bytes, &mut ctx)
+        .unwrap();
+    assert_eq!(bytes, [0, 3, 0, 0, 0, 1, 0, 0]);
+    assert_eq!(ids.0, ["air", "stone"]);
+
+    let mut ctx = DeserializeCtx {
+        user: Some(&mut ids),
+        ..Default::default()
+    };
+    let des = Nodes::mt_deserialize_with::<DefCfg>(&mut std::io::Cursor::new(&bytes), &mut ctx);
+    assert_eq!(des.unwrap(), nodes);
+
+    // context-free calls have no table to map through
+    let err = nodes.mt_serialize::<DefCfg>(&mut Vec::new()).unwrap_err();
+    assert!(err
+        .to_string()
+        .ends_with("missing mt_ser::tests::NodeIds context"));
+    let err = Nodes::mt_deserialize::<DefCfg>(&mut std::io::Cursor::new(&bytes)).unwrap_err();
+    assert!(matches!(err.inner(), DeserializeError::MissingContext(_)));
+
+    // a context of another type is reported as such
+    let mut other = 0u32;
+    let mut ctx = DeserializeCtx {
+        user: Some(&mut other),
+        ..Default::default()
+    };
+    let err = Nodes::mt_deserialize_with::<DefCfg>(&mut std::io::Cursor::new(&bytes), &mut ctx)
+        .unwrap_err();
+    assert!(matches!(
+        err.inner(),
+        DeserializeError::WrongContextType("mt_ser::tests::NodeIds")
+    ));
+    assert!(err
+        .to_string()
+        .ends_with("context is not a mt_ser::tests::NodeIds"));
+}
+
+#[test]
+fn test_trace() {
+    let outer = Outer {
+        id: 7,
+        style: vec![Inner { len: vec![1, 2] }],
+    };
+    let mut bytes = Vec::new();
+    outer.mt_serialize::<DefCfg>(&mut bytes).unwrap();
+
+    let (res, trace) = mt_deserialize_traced::<DefCfg, Outer>(&mut bytes.as_slice());
+    assert_eq!(res.unwrap(), outer);
+
+    let fields: Vec<_> = trace
+        .entries()
+        .iter()
+        .map(|x| (x.field, x.depth, x.start, x.end))
+        .collect();
+    assert_eq!(
+        fields,
+        [("id", 0, 0, 4), ("style", 0, 4, 11), ("len", 1, 8, 11)]
+    );
+    assert_eq!(trace.entries()[2].value.as_deref(), Some("[1, 2]"));
+
+    let pad = |hex: &str| format!("{hex:47}");
+    assert_eq!(
+        trace.hexdump(&bytes),
+        format!(
+            "00000000  {}  Outer.id = 7\n\
+             00000004  {}  Outer.style = [Inner {{ len: [1, 2] }}]\n\
+             00000008  {}    Inner.len = [1, 2]\n",
+            pad("00 00 00 07"),
+            pad("00 00 00 01"),
+            pad("02 01 02"),
+        )
+    );
+
+    // up to the point of failure
+    let (res, trace) = mt_deserialize_traced::<DefCfg, Outer>(&mut &bytes[..10]);
+    assert!(res.is_err());
+    let dump = trace.hexdump(&bytes[..10]);
+    assert!(dump.ends_with(&format!(
+        "Outer.style (failed)\n00000008  {}    Inner.len (failed)\n",
+        pad("02 01")
+    )));
+
+    // the ctx limits still apply while tracing
+    let mut ctx = DeserializeCtx {
+        limits: Limits::new(1, usize::MAX, usize::MAX),
+        ..Default::default()
+    };
+    let (res, trace) = mt_deserialize_traced_with::<DefCfg, Outer>(&mut bytes.as_slice(), &mut ctx);
+    assert!(matches!(
+        res.unwrap_err().inner(),
+        DeserializeError::LimitExceeded(Limit::Len)
+    ));
+    assert!(trace.hexdump(&bytes).ends_with("Inner.len (failed)\n"));
+    assert!(ctx.trace.is_none());
+}
+
+#[test]
+fn test_field_path() {
+    let mut bytes = Vec::new();
+    1u32.mt_serialize::<DefCfg>(&mut bytes).unwrap();
+    4u32.mt_serialize::<DefCfg>(&mut bytes).unwrap();
+    bytes.extend_from_slice(&[0, 0, 0, 5, 1]);
+
+    let err = Outer::mt_deserialize::<DefCfg>(&mut std::io::Cursor::new(bytes)).unwrap_err();
+    assert_eq!(err.path().unwrap().to_string(), "Outer.style[3].len[1]");
+    assert!(matches!(err.inner(), DeserializeError::UnexpectedEof));
+
+    let outer = Outer {
+        id: 0,
+        style: vec![Inner { len: vec![] }, Inner { len: vec![0; 256] }],
+    };
+    let err = outer.mt_serialize::<DefCfg>(&mut Vec::new()).unwrap_err();
+    assert_eq!(
+        err.to_string().split(':').next(),
+        Some("Outer.style[1].len")
+    );
+    assert!(matches!(err.inner(), SerializeError::TooBig(_)));
+}
+
+#[derive(Debug, PartialEq, MtSerialize)]
+struct Consts {
+    #[mt(const_before = "0xABu8", const_after = "[1u16, 2u16]")]
+    a: u16,
+    #[mt(len = "Utf16<u8>")]
+    name: String,
+}
+
+#[test]
+fn test_serialized_len() {
+    fn len_of<C: MtCfg, T: MtSerialize>(item: &T) -> usize {
+        let mut writer = Vec::new();
+        item.mt_serialize::<C>(&mut writer).unwrap();
+        assert_eq!(item.mt_serialized_len::<C>().unwrap(), Some(writer.len()));
+        writer.len()
+    }
+
+    assert_eq!(len_of::<DefCfg, _>(&vec![1u32, 2, 3]), 2 + 12);
+    assert_eq!(len_of::<(), _>(&vec![1u32, 2, 3]), 12);
+    assert_eq!(len_of::<Utf16<u32>, _>(&String::from("µ😀")), 4 + 6);
+    assert_eq!(len_of::<(u8, Utf16), _>(&vec!["µ😀", "a"]), 1 + 8 + 4);
+    assert_eq!(len_of::<DefCfg, _>(&(1u8, Some(2u16), None::<u32>)), 3);
+
+    let item = Consts {
+        a: 1,
+        name: "ab".into(),
+    };
+    assert_eq!(len_of::<DefCfg, _>(&item), 1 + 2 + 4 + 1 + 4);
+
+    #[derive(MtSerialize)]
+    struct Sized {
+        #[mt(size = "u32")]
+        names: Vec<String>,
+        #[mt(size = "u8", len = "u8", const_after = "0u8")]
+        style: Vec<Inner>,
+    }
+
+    let item = Sized {
+        names: vec!["ab".into()],
+        style: vec![Inner { len: vec![1, 2] }, Inner { len: vec![] }],
+    };
+    assert_eq!(len_of::<DefCfg, _>(&item), 4 + 2 + 4 + 1 + 1 + 3 + 1 + 1);
+
+    // compressed output is only known after encoding
+    let item = Compressed {
+        a: Inner { len: vec![1, 2] },
+        b: Inner { len: vec![3] },
+        c: 4,
+    };
+    assert!(matches!(item.mt_serialized_len::<DefCfg>(), Ok(None)));
+
+    let long: Vec<_> = (0..=256).collect();
+    assert!(matches!(
+        long.mt_serialized_len::<u8>(),
+        Err(SerializeError::TooBig(_))
+    ));
+
+    // fields present depending on the context version
+    let hello = Hello {
+        id: 1,
+        name: "ab".into(),
+        legacy: 2,
+    };
+    let len_at = |version| {
+        let mut ctx = SerializeCtx {
+            version,
+            ..Default::default()
+        };
+        hello.mt_serialized_len_with::<DefCfg>(&mut ctx).unwrap()
+    };
+    assert_eq!(len_at(Some(36)), Some(1 + 1));
+    assert_eq!(len_at(Some(37)), Some(1 + 4 + 1));
+    assert_eq!(len_at(None), Some(1 + 4));
+    assert_eq!(hello.mt_serialized_len::<DefCfg>().unwrap(), len_at(None));
+}
+
+#[derive(Debug, PartialEq, MtSerialize, MtDeserialize)]
+struct Sections {
+    a: u16,
+    #[mt(size = "u16")]
+    inner: Inner,
+    #[mt(zlib)]
+    packed: Inner,
+}
+
+#[test]
+fn test_offset() {
+    let mut bytes = Vec::new();
+    Outer {
+        id: 1,
+        style: vec![Inner { len: vec![1, 2] }],
+    }
+    .mt_serialize::<DefCfg>(&mut bytes)
+    .unwrap();
+    bytes.pop();
+
+    let err =
+        mt_deserialize_counted::<DefCfg, Outer>(&mut std::io::Cursor::new(&bytes)).unwrap_err();
+    assert_eq!(err.offset(), Some(bytes.len() as u64));
+    assert_eq!(err.path().unwrap().to_string(), "Outer.style[0].len[1]");
+
+    let item = Sections {
+        a: 1,
+        inner: Inner { len: vec![1, 2] },
+        packed: Inner { len: vec![3] },
+    };
+    let mut bytes = Vec::new();
+    item.mt_serialize::<DefCfg>(&mut bytes).unwrap();
+
+    let mut reader = CountRead::new(std::io::Cursor::new(&bytes));
+    assert_eq!(
+        item,
+        Sections::mt_deserialize::<DefCfg>(&mut reader).unwrap()
+    );
+    assert_eq!(reader.pos(), bytes.len() as u64);
+
+    // size section ends at byte 7, the zlib stream header is truncated
+    bytes.truncate(8);
+    let err =
+        mt_deserialize_counted::<DefCfg, Sections>(&mut std::io::Cursor::new(&bytes)).unwrap_err();
+    assert_eq!(err.offset(), Some(8));
+    assert_eq!(err.section_offset(), Some(("zlib", 0)));
+    assert_eq!(err.path().unwrap().to_string(), "Sections.packed.len");
+
+    // offsets inside a section refer to the decompressed data
+    let item = Sections {
+        a: 1,
+        inner: Inner { len: vec![1, 2] },
+        packed: Inner { len: vec![1, 2, 3] },
+    };
+    let mut bytes = Vec::new();
+    item.mt_serialize::<DefCfg>(&mut bytes).unwrap();
+    // claim four elements where the section only has three
+    let mut packed = Vec::new();
+    Inner {
+        len: vec![1, 2, 3, 4],
+    }
+    .mt_serialize::<DefCfg>(&mut packed)
+    .unwrap();
+    packed.pop();
+    let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+    std::io::Write::write_all(&mut encoder, &packed).unwrap();
+    bytes.truncate(7);
+    bytes.extend(encoder.finish().unwrap());
+
+    let err =
+        mt_deserialize_counted::<DefCfg, Sections>(&mut std::io::Cursor::new(&bytes)).unwrap_err();
+    assert_eq!(err.section_offset(), Some(("zlib", 4)));
+    assert_eq!(err.path().unwrap().to_string(), "Sections.packed.len[3]");
+    assert!(err
+        .to_string()
+        .contains("at byte 4 of the decompressed zlib section"));
+}
+
+#[derive(Debug, PartialEq, MtSerialize, MtDeserialize)]
+#[cfg_attr(feature = "async", derive(MtAsyncSerialize, MtAsyncDeserialize))]
+struct Owned {
+    name: String,
+    #[mt(len = "u8")]
+    data: Vec<u8>,
+    #[mt(len = "Utf16")]
+    utf16: String,
+    #[mt(size = "u16")]
+    names: Vec<String>,
+    #[mt(size = "u32", zlib)]
+    packed: String,
+    rest: Vec<u16>,
+}
+
+#[derive(Debug, PartialEq, MtDeserializeBorrowed)]
+struct Borrowed<'a> {
+    name: &'a str,
+    #[mt(len = "u8")]
+    data: &'a [u8],
+    #[mt(len = "Utf16")]
+    utf16: std::borrow::Cow<'a, str>,
+    #[mt(size = "u16")]
+    names: Vec<std::borrow::Cow<'a, str>>,
+    #[mt(size = "u32", zlib)]
+    packed: String,
+    rest: Vec<u16>,
+}
+
+#[test]
+fn test_borrowed() {
+    let item = Owned {
+        name: "name".into(),
+        data: vec![1, 2, 3],
+        utf16: "µ ß 私 😀".into(),
+        names: vec!["a".into(), "bc".into()],
+        packed: "packed".into(),
+        rest: vec![4, 5],
+    };
+
+    let mut bytes = Vec::new();
+    item.mt_serialize::<DefCfg>(&mut bytes).unwrap();
+
+    let mut reader = bytes.as_slice();
+    let des = Borrowed::mt_deserialize_borrowed::<DefCfg>(&mut reader).unwrap();
+    assert!(reader.is_empty());
+
+    assert_eq!(des.name, item.name);
+    assert_eq!(des.name.as_ptr(), bytes[2..].as_ptr());
+    assert_eq!(des.data, item.data);
+    assert_eq!(des.utf16, item.utf16);
+    assert_eq!(des.names, item.names);
+    assert!(matches!(des.names[1], std::borrow::Cow::Borrowed(_)));
+    assert_eq!(des.packed, item.packed);
+    assert_eq!(des.rest, item.rest);
+
+    let err = Borrowed::mt_deserialize_borrowed::<DefCfg>(&mut &bytes[..8]).unwrap_err();
+    assert_eq!(err.path().unwrap().to_string(), "Borrowed.data");
+    assert!(matches!(err.inner(), DeserializeError::UnexpectedEof));
+}
+
+#[cfg(feature = "async")]
+#[derive(Debug, PartialEq, MtSerialize, MtDeserialize, MtAsyncSerialize, MtAsyncDeserialize)]
+#[repr(u8)]
+enum Packet {
+    Empty,
+    #[mt(const_before = "7u8")]
+    Outer(Outer),
+    Owned(Owned),
+    Compressed(Compressed),
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn test_async() {
+    let items = [
+        Packet::Empty,
+        Packet::Outer(Outer {
+            id: 1,
+            style: vec![Inner { len: vec![1, 2] }],
+        }),
+        Packet::Owned(Owned {
+            name: "name".into(),
+            data: vec![1, 2, 3],
+            utf16: "µ ß 私 😀".into(),
+            names: vec!["a".into(), "bc".into()],
+            packed: "packed".into(),
+            rest: vec![4, 5],
+        }),
+        Packet::Compressed(Compressed {
+            a: Inner { len: vec![1, 2] },
+            b: Inner { len: vec![3] },
+            c: 4,
+        }),
+    ];
+
+    for item in items {
+        let mut bytes = Vec::new();
+        item.mt_serialize_async::<DefCfg, _>(&mut bytes)
+            .await
+            .unwrap();
+
+        // the encoders are the same libraries, so the bytes are too
+        let mut sync = Vec::new();
+        item.mt_serialize::<DefCfg>(&mut sync).unwrap();
+        assert_eq!(bytes, sync);
+
+        let mut reader = bytes.as_slice();
+        let des = Packet::mt_deserialize_async::<DefCfg, _>(&mut reader)
+            .await
+            .unwrap();
+        assert_eq!(item, des);
+        assert!(reader.is_empty());
+    }
+
+    let err = Packet::mt_deserialize_async::<DefCfg, _>(&mut &[4u8][..])
+        .await
+        .unwrap_err();
+    assert!(matches!(err, DeserializeError::InvalidEnum("Packet", _)));
+
+    // versions and user context come from the DeserializeCtx, like with MtDeserialize
+    let mut ctx = DeserializeCtx {
+        version: Some(36),
+        ..Default::default()
+    };
+    let hello = mt_deserialize_async_with::<Hello, DefCfg, _>(&mut &[1u8, 2][..], &mut ctx)
+        .await
+        .unwrap();
+    assert_eq!(
+        hello,
+        Hello {
+            id: 1,
+            name: String::new(),
+            legacy: 2,
+        }
+    );
+
+    let caps = mt_deserialize_async::<ToolCaps, DefCfg, _>(&mut &[3u8, 0, 1, 0, 2, 3][..])
+        .await
+        .unwrap();
+    assert_eq!(
+        caps,
+        ToolCaps {
+            interval: 1,
+            damage: 2,
+            legacy: 3,
+        }
+    );
+
+    let mut ids = NodeIds(vec!["air".into(), "stone".into()]);
+    let mut ctx = DeserializeCtx {
+        user: Some(&mut ids),
+        ..Default::default()
+    };
+    let nodes =
+        mt_deserialize_async_with::<Nodes, DefCfg, _>(&mut &[0u8, 2, 0, 1, 0, 0][..], &mut ctx)
+            .await
+            .unwrap();
+    assert_eq!(nodes.names, ["stone", "air"]);
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn test_async_compressed() {
+    let data: Vec<u8> = (0..4096u32).map(|i| (i * i / 7 % 13) as u8).collect();
+    let item = Levels {
+        fast: data.clone(),
+        stored: data.clone(),
+        wide: data,
+    };
+
+    // finishing a section must not shut down the writer, the next message follows it
+    let (mut writer, mut reader) = tokio::io::duplex(1 << 16);
+    let mut ctx = SerializeCtx {
+        zlib_level: Some(0),
+        ..Default::default()
+    };
+    item.mt_serialize_async::<DefCfg, _>(&mut writer)
+        .await
+        .unwrap();
+    item.mt_serialize_async_with::<DefCfg, _>(&mut writer, &mut ctx)
+        .await
+        .unwrap();
+    drop(writer);
+
+    let mut bytes = Vec::new();
+    tokio::io::AsyncReadExt::read_to_end(&mut reader, &mut bytes)
+        .await
+        .unwrap();
+
+    let mut sync = Vec::new();
+    item.mt_serialize::<DefCfg>(&mut sync).unwrap();
+    let first = sync.len();
+    let mut ctx = SerializeCtx {
+        zlib_level: Some(0),
+        ..Default::default()
+    };
+    item.mt_serialize_with::<DefCfg>(&mut sync, &mut ctx)
+        .unwrap();
+    assert_eq!(bytes, sync);
+
+    // wide has no size, so its decoder may read ahead into whatever follows
+    for mut reader in [&bytes[..first], &bytes[first..]] {
+        let des = Levels::mt_deserialize_async::<DefCfg, _>(&mut reader)
+            .await
+            .unwrap();
+        assert_eq!(item, des);
+    }
+
+    // the codec depends on the version, like with MtSerialize
+    let block = Block {
+        nodes: vec![1, 2, 3],
+    };
+    for version in [28, 29] {
+        let mut ctx = SerializeCtx {
+            version: Some(version),
+            ..Default::default()
+        };
+        let mut bytes = Vec::new();
+        block
+            .mt_serialize_async_with::<DefCfg, _>(&mut bytes, &mut ctx)
+            .await
+            .unwrap();
+        let mut sync = Vec::new();
+        block
+            .mt_serialize_with::<DefCfg>(&mut sync, &mut ctx)
+            .unwrap();
+        assert_eq!(bytes, sync);
+    }
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn test_async_max_decompressed() {
+    let mut bytes = Vec::new();
+    Bomb {
+        capped: vec![0; 100],
+        global: vec![0; 1000],
+    }
+    .mt_serialize::<DefCfg>(&mut bytes)
+    .unwrap();
+
+    let err = mt_deserialize_async::<Bomb, DefCfg, _>(&mut bytes.as_slice())
+        .await
+        .unwrap_err();
+    assert_eq!(err.path().unwrap().to_string(), "Bomb.capped[62]");
+    assert!(matches!(
+        err.inner(),
+        DeserializeError::LimitExceeded(Limit::Decompressed)
+    ));
+
+    // the global cap covers the section without max_decompressed
+    let mut bytes = Vec::new();
+    Bomb {
+        capped: vec![],
+        global: vec![0; 60000],
+    }
+    .mt_serialize::<DefCfg>(&mut bytes)
+    .unwrap();
+    assert!(bytes.len() < 256);
+
+    let mut limits = Limits::default();
+    limits.max_decompressed = 500;
+    let mut ctx = DeserializeCtx {
+        limits,
+        ..Default::default()
+    };
+    let err = mt_deserialize_async_with::<Bomb, DefCfg, _>(&mut bytes.as_slice(), &mut ctx)
+        .await
+        .unwrap_err();
+    assert_eq!(err.path().unwrap().to_string(), "Bomb.global[498]");
+    assert!(matches!(
+        err.inner(),
+        DeserializeError::LimitExceeded(Limit::Decompressed)
+    ));
+}
+
+#[cfg(feature = "arbitrary")]
+#[test]
+fn test_arbitrary() {
+    use arbitrary::{Arbitrary, Unstructured};
+
+    for seed in 0..64u8 {
+        let data: Vec<u8> = (0..512u32).map(|i| (i as u8).wrapping_mul(seed)).collect();
+        let item = Compressed::arbitrary(&mut Unstructured::new(&data)).unwrap();
+
+        // len = u8 fields reject more than 255 elements
+        if item.a.len.len() <= 255 && item.b.len.len() <= 255 {
+            assert_eq!(item, reserialize::<DefCfg, _>(&item));
+        }
+    }
+}
+
+#[cfg(feature = "proptest")]
+#[derive(Debug, PartialEq, MtSerialize, MtDeserialize, MtStrategy)]
+#[repr(u8)]
+enum Message {
+    Outer(Outer),
+    Compressed(Compressed),
+    Hello(Hello),
+    #[mt(const_before = "7u8")]
+    Caps(ToolCaps),
+    Text(
+        #[mt(len = "Utf16<u8>")] String,
+        #[mt(len = "u8")] Vec<String>,
+    ),
+    MapNode(MapNode),
+    // only the last one may be None
+    Optional(
+        Option<u16>,
+        Option<(u8, Option<u8>)>,
+        Vec<Option<u8>>,
+        Option<u8>,
+    ),
+}
+
+#[cfg(feature = "proptest")]
+proptest::proptest! {
+    #[test]
+    fn test_strategy(msg in Message::mt_strategy::<DefCfg>()) {
+        // fields removed by the newest version or by max_version are left at their default
+        assert_mt_roundtrip::<DefCfg, _>(&msg);
+
+        // the computed length matches whenever it does not depend on compressed output
+        if let Some(len) = msg.mt_serialized_len::<DefCfg>().unwrap() {
+            let mut bytes = Vec::new();
+            msg.mt_serialize::<DefCfg>(&mut bytes).unwrap();
+            proptest::prop_assert_eq!(len, bytes.len());
+        }
+    }
+
+    #[test]
+    fn test_strategy_len(
+        items in Vec::<u8>::mt_strategy::<u8>(),
+        st in String::mt_strategy::<u8>(),
+        utf16 in String::mt_strategy::<Utf16<u8>>(),
+    ) {
+        proptest::prop_assert!(items.len() <= 255);
+        proptest::prop_assert!(st.len() <= 255);
+        proptest::prop_assert!(utf16.encode_utf16().count() <= 255);
+    }
+}
+
+#[cfg(feature = "random")]
+#[derive(Debug, PartialEq, MtSerialize, MtDeserialize, GenerateRandom)]
+#[mt(string_repr)]
+enum Weather {
+    Clear,
+    HeavyRain,
+}
+
+#[cfg(feature = "random")]
+#[derive(Debug, PartialEq, MtSerialize, MtDeserialize, GenerateRandom)]
+struct Corpus {
+    weather: Weather,
+    outer: Outer,
+    hello: Hello,
+    caps: ToolCaps,
+    icon: Option<u16>,
+    #[mt(len = "u8")]
+    names: Vec<String>,
+    #[mt(len = "Utf16<u8>")]
+    title: String,
+    footer: Option<(Option<u8>, u8)>,
+}
+
+#[cfg(feature = "random")]
+#[test]
+fn test_generate_random() {
+    for seed in 0..32 {
+        let item: Corpus = mt_generate_seeded::<DefCfg, _>(seed);
+        assert_eq!(item, mt_generate_seeded::<DefCfg, _>(seed));
+        assert_eq!(item, reserialize::<DefCfg, _>(&item));
+
+        assert!(item.names.len() <= 255);
+        assert!(item.title.encode_utf16().count() <= 255);
+        // only the last field can be None, it would read the fields after it otherwise
+        assert!(item.icon.is_some());
+        if let Some((a, _)) = item.footer {
+            assert!(a.is_some());
+        }
+        // removed by the newest version and by max_version
+        assert_eq!((item.hello.legacy, item.caps.legacy), (0, 0));
+    }
+
+    let mut rng = mt_seeded_rng(0);
+    let weather: Vec<Weather> = (0..64)
+        .map(|_| Weather::generate_random::<DefCfg>(&mut rng))
+        .collect();
+    assert!(weather.contains(&Weather::Clear) && weather.contains(&Weather::HeavyRain));
+}
+
+#[cfg(feature = "serde")]
+#[derive(
+    Debug, PartialEq, ::serde::Serialize, ::serde::Deserialize, MtSerialize, MtDeserialize,
+)]
+#[repr(u8)]
+enum Shape {
+    Empty,
+    Box(u16),
+    Mesh { name: String, scale: f32 },
+}
+
+#[cfg(feature = "serde")]
+#[derive(
+    Debug, PartialEq, ::serde::Serialize, ::serde::Deserialize, MtSerialize, MtDeserialize,
+)]
+#[serde(rename_all = "snake_case")]
+#[mt(string_repr)]
+enum Light {
+    Sun,
+    NightLight,
+}
+
+#[cfg(feature = "serde")]
+#[derive(
+    Debug, PartialEq, ::serde::Serialize, ::serde::Deserialize, MtSerialize, MtDeserialize,
+)]
+#[repr(u8)]
+enum Level {
+    Low = 2,
+    High = 5,
+}
+
+#[cfg(feature = "serde")]
+#[derive(
+    Debug, PartialEq, ::serde::Serialize, ::serde::Deserialize, MtSerialize, MtDeserialize,
+)]
+struct Both {
+    id: u16,
+    flag: bool,
+    #[serde(with = "crate::serde::len_u8")]
+    #[mt(len = "u8")]
+    data: Vec<u8>,
+    names: Vec<String>,
+    #[serde(with = "crate::serde::utf16_u32")]
+    #[mt(len = "Utf16<u32>")]
+    text: String,
+    pos: [f32; 3],
+    pair: (i8, u64),
+    shapes: Vec<Shape>,
+    #[serde(with = "crate::serde::repr_str")]
+    light: Light,
+    table: std::collections::BTreeMap<u8, Vec<i32>>,
+    #[serde(with = "crate::serde::len_to_end")]
+    #[mt(len = "()")]
+    rest: Vec<u16>,
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_serde() {
+    let item = Both {
+        id: 1,
+        flag: true,
+        data: vec![2, 3],
+        names: vec!["a".into(), "µ".into()],
+        text: "私 😀".into(),
+        pos: [1.0, -2.5, 3.0],
+        pair: (-4, 5),
+        shapes: vec![
+            Shape::Empty,
+            Shape::Box(6),
+            Shape::Mesh {
+                name: "m".into(),
+                scale: 0.5,
+            },
+        ],
+        light: Light::NightLight,
+        table: [(7, vec![8, -9]), (10, vec![])].into(),
+        rest: vec![11, 12],
+    };
+
+    let mut bytes = Vec::new();
+    item.mt_serialize::<DefCfg>(&mut bytes).unwrap();
+    assert_eq!(crate::serde::to_vec(&item).unwrap(), bytes);
+    assert_eq!(crate::serde::from_slice::<Both>(&bytes).unwrap(), item);
+
+    // the config applies to the value itself, its elements get the default again
+    let cfg = crate::serde::SerdeCfg::new(
+        crate::serde::SerdeLen::U8,
+        true,
+        crate::serde::SerdeRepr::U8,
+    );
+    let names = vec![String::from("ab")];
+    let mut bytes = Vec::new();
+    crate::serde::to_writer_with(&mut bytes, &names, cfg).unwrap();
+    assert_eq!(bytes, [1, 0, 2, b'a', b'b']);
+    assert_eq!(
+        crate::serde::from_reader_with::<Vec<String>>(
+            &mut bytes.as_slice(),
+            cfg,
+            &mut DeserializeCtx::default()
+        )
+        .unwrap(),
+        names
+    );
+
+    let err = crate::serde::from_slice::<Shape>(&[3]).unwrap_err();
+    assert!(matches!(err, DeserializeError::InvalidEnum("Shape", _)));
+
+    // serde only knows the variant index, not the discriminant
+    let mut bytes = Vec::new();
+    Level::High.mt_serialize::<DefCfg>(&mut bytes).unwrap();
+    assert_eq!(bytes, [5]);
+    assert_eq!(crate::serde::to_vec(&Level::High).unwrap(), [1]);
+    assert!(crate::serde::from_slice::<Level>(&bytes).is_err());
+
+    // the limits of the ctx apply, and are left intact by errors
+    let mut ctx = DeserializeCtx {
+        limits: Limits::new(usize::MAX, usize::MAX, 1),
+        ..Default::default()
+    };
+    let cfg = crate::serde::SerdeCfg::default();
+    let err = crate::serde::from_reader_with::<Vec<Vec<u8>>>(
+        &mut [0, 1, 0, 1, 7].as_slice(),
+        cfg,
+        &mut ctx,
+    )
+    .unwrap_err();
+    assert!(matches!(
+        err.inner(),
+        DeserializeError::LimitExceeded(Limit::Depth)
+    ));
+    assert_eq!(
+        crate::serde::from_reader_with::<Vec<u8>>(&mut [0, 1, 7].as_slice(), cfg, &mut ctx)
+            .unwrap(),
+        [7]
+    );
+}
+
+type Yaw = Fixed<f32, u16, 100, Round, Saturate>;
+
+#[derive(Debug, PartialEq, MtSerialize, MtDeserialize)]
+#[cfg_attr(feature = "proptest", derive(MtStrategy))]
+#[cfg_attr(feature = "random", derive(GenerateRandom))]
+struct Look {
+    speed: F1000,
+    yaw: Yaw,
+}
+
+#[test]
+fn test_fixed_point() {
+    let look = Look {
+        speed: F1000::new(-1.5),
+        yaw: Yaw::new(359.99),
+    };
+    let mut bytes = Vec::new();
+    look.mt_serialize::<DefCfg>(&mut bytes).unwrap();
+    assert_eq!(bytes, [0xFF, 0xFF, 0xFA, 0x24, 0x8C, 0x9F]);
+    assert_eq!(look, reserialize::<DefCfg, _>(&look));
+
+    // rounding mode, 0.7f32 is 0.699999988
+    assert_eq!(F1000::new(0.7).to_int().unwrap(), 700);
+    assert_eq!(
+        Fixed::<f32, i32, 1000, Trunc>::new(0.7).to_int().unwrap(),
+        699
+    );
+    assert_eq!(F1000::new(-0.0005).to_int().unwrap(), -1);
+
+    // out of range
+    assert!(matches!(
+        F1000::new(3e6).mt_serialize::<DefCfg>(&mut Vec::new()),
+        Err(SerializeError::OutOfRange(..))
+    ));
+    assert!(F1000::new(f32::NAN).to_int().is_err());
+    assert_eq!(Yaw::new(-1.0).to_int().unwrap(), 0);
+    assert_eq!(Yaw::new(1000.0).to_int().unwrap(), u16::MAX);
+    assert_eq!(
+        Fixed::<f64, u64, 1, Round>::new(2f64.powi(64))
+            .to_int()
+            .ok(),
+        None
+    );
+}
+
+#[cfg(feature = "color")]
+#[test]
+fn test_color() {
+    let c: Color = "#11223344".parse().unwrap();
+    assert_eq!(c, Color::new(0x11, 0x22, 0x33, 0x44));
+    assert_eq!(u32::from(c), 0x44112233);
+    assert_eq!(<[u8; 4]>::from(c), [0x11, 0x22, 0x33, 0x44]);
+    assert_eq!(Color::from(0x44112233), c);
+    assert_eq!(c.to_string(), "#11223344");
+
+    let mut bytes = Vec::new();
+    c.mt_serialize::<DefCfg>(&mut bytes).unwrap();
+    assert_eq!(bytes, [0x44, 0x11, 0x22, 0x33]);
+    assert_eq!(c, reserialize::<DefCfg, _>(&c));
+
+    assert_eq!("#abc".parse(), Ok(Color::rgb(0xAA, 0xBB, 0xCC)));
+    assert_eq!("#abc8".parse(), Ok(Color::new(0xAA, 0xBB, 0xCC, 0x88)));
+    assert_eq!("#A0B1C2".parse(), Ok(Color::rgb(0xA0, 0xB1, 0xC2)));
+    for bad in ["", "#", "112233", "#12345", "#1122334g", "#+1+2+3", "#ÿÿ"] {
+        assert!(bad.parse::<Color>().is_err(), "{bad}");
+    }
+}
+
+// the 24 rotations of a facedir node
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Facedir(u8);
+
+impl MtBits for Facedir {
+    fn to_bits(&self) -> u32 {
+        self.0.into()
+    }
+
+    fn from_bits(bits: u32) -> Result<Self, DeserializeError> {
+        if bits < 24 {
+            Ok(Self(bits as u8))
+        } else {
+            Err(DeserializeError::InvalidEnum("Facedir", Box::new(bits)))
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, MtSerialize, MtDeserialize, MtDeserializeBorrowed)]
+#[cfg_attr(feature = "async", derive(MtAsyncSerialize, MtAsyncDeserialize))]
+#[cfg_attr(feature = "proptest", derive(MtStrategy))]
+#[cfg_attr(feature = "random", derive(GenerateRandom))]
+struct MapNode {
+    content: u16,
+    #[mt(bits = 4)]
+    light_day: u8,
+    #[mt(bits = 4)]
+    light_night: u8,
+    #[mt(bits = 3)]
+    color: u8,
+    #[mt(bits = 5)]
+    facedir: Facedir,
+    #[mt(bits = 1)]
+    lit: bool,
+    #[mt(bits = 12)]
+    level: u16,
+    #[mt(bits = 3)]
+    waving: u8,
+}
+
+#[test]
+fn test_bits() {
+    let node = MapNode {
+        content: 0x1234,
+        light_day: 15,
+        light_night: 3,
+        color: 5,
+        facedir: Facedir(23),
+        lit: true,
+        level: 0xABC,
+        waving: 2,
+    };
+    let mut bytes = Vec::new();
+    node.mt_serialize::<DefCfg>(&mut bytes).unwrap();
+    assert_eq!(bytes, [0x12, 0x34, 0xF3, 0xB7, 0xD5, 0xE2]);
+    assert_eq!(node, reserialize::<DefCfg, _>(&node));
+    assert_eq!(
+        node,
+        MapNode::mt_deserialize_borrowed::<DefCfg>(&mut bytes.as_slice()).unwrap()
+    );
+
+    let err = MapNode {
+        light_night: 16,
+        ..node
+    }
+    .mt_serialize::<DefCfg>(&mut Vec::new())
+    .unwrap_err();
+    assert_eq!(err.path().unwrap().to_string(), "MapNode.light_night");
+    assert!(matches!(err.inner(), SerializeError::BitsOverflow(16, 4)));
+
+    // facedir 24
+    let err = MapNode::mt_deserialize::<DefCfg>(&mut [0, 0, 0, 0x18, 0, 0].as_slice()).unwrap_err();
+    assert_eq!(err.path().unwrap().to_string(), "MapNode.facedir");
+
+    let (_, trace) = mt_deserialize_traced::<DefCfg, MapNode>(&mut bytes.as_slice());
+    let fields: Vec<_> = trace
+        .entries()
+        .iter()
+        .map(|e| (e.field, e.start, e.end))
+        .collect();
+    assert_eq!(
+        fields,
+        [
+            ("content", 0, 2),
+            ("light_day|light_night", 2, 3),
+            ("color|facedir", 3, 4),
+            ("lit|level|waving", 4, 6),
+        ]
+    );
+
+    #[cfg(feature = "random")]
+    for seed in 0..32 {
+        let node: MapNode = mt_generate_seeded::<DefCfg, _>(seed);
+        assert_eq!(node, reserialize::<DefCfg, _>(&node));
+    }
+
+    // the full and an empty range
+    #[cfg(feature = "random")]
+    {
+        let mut rng = mt_seeded_rng(0);
+        assert_eq!(mt_generate_bits::<u32>(&mut rng, 0), 0);
+        mt_generate_bits::<u32>(&mut rng, 32);
+
+        // rejects all but one in 2^32, so the random draws give up
+        let only_zero = mt_generate_bits::<OnlyZero>(&mut rng, 32);
+        assert_eq!(only_zero.to_bits(), 0);
+    }
+}
+
+#[cfg(feature = "random")]
+struct OnlyZero;
+
+#[cfg(feature = "random")]
+impl MtBits for OnlyZero {
+    fn to_bits(&self) -> u32 {
+        0
+    }
+
+    fn from_bits(bits: u32) -> Result<Self, DeserializeError> {
+        if bits == 0 {
+            Ok(Self)
+        } else {
+            Err(DeserializeError::InvalidEnum("OnlyZero", Box::new(bits)))
+        }
+    }
+}
@@ -0,0 +1,165 @@
+use super::*;
+use alloc::format;
+use core::{
+    fmt::Write as _,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+// one field decoded by derived code, see mt_deserialize_traced
+// (offsets within compressed sections refer to the compressed input consumed so far)
+#[derive(Debug, Clone)]
+pub struct TraceEntry {
+    pub path: &'static str, // the containing type, e.g. "Outer" or "Enum::Variant"
+    pub field: &'static str,
+    pub depth: usize, // number of enclosing traced fields
+    pub start: u64,
+    pub end: u64,
+    pub value: Option<String>, // Debug output, None if the type has none
+    pub failed: bool,
+}
+
+// field by field record of a deserialization, carried by DeserializeCtx
+#[derive(Debug, Default)]
+pub struct Trace {
+    entries: Vec<TraceEntry>,
+    open: Vec<usize>,
+    pos: Arc<AtomicU64>, // shared with TraceRead, Arc to keep DeserializeCtx Send
+}
+
+impl Trace {
+    pub fn entries(&self) -> &[TraceEntry] {
+        &self.entries
+    }
+
+    pub fn enter(&mut self, path: &'static str, field: &'static str) {
+        self.open.push(self.entries.len());
+        self.entries.push(TraceEntry {
+            path,
+            field,
+            depth: self.open.len() - 1,
+            start: self.pos.load(Ordering::Relaxed),
+            end: self.pos.load(Ordering::Relaxed),
+            value: None,
+            failed: false,
+        });
+    }
+
+    // closes the field entered last, value is None if it failed
+    pub fn leave(&mut self, value: Option<Option<String>>) {
+        let Some(i) = self.open.pop() else {
+            return;
+        };
+
+        let entry = &mut self.entries[i];
+        entry.end = self.pos.load(Ordering::Relaxed);
+        entry.failed = value.is_none();
+        entry.value = value.flatten();
+    }
+
+    // bytes must be what was decoded, each field gets its bytes in hex (up to the
+    // first nested field, if any) with its name and value in the margin
+    pub fn hexdump(&self, bytes: &[u8]) -> String {
+        let mut out = String::new();
+
+        for (i, entry) in self.entries.iter().enumerate() {
+            let mut label = format!(
+                "{:indent$}{}.{}",
+                "",
+                entry.path,
+                entry.field,
+                indent = entry.depth * 2
+            );
+            if let Some(value) = &entry.value {
+                let _ = write!(label, " = {value}");
+            }
+            if entry.failed {
+                label.push_str(" (failed)");
+            }
+
+            let clamp = |x: u64| usize::try_from(x).unwrap_or(usize::MAX).min(bytes.len());
+            let end = match self.entries.get(i + 1) {
+                Some(next) if next.depth > entry.depth => next.start,
+                _ => entry.end,
+            };
+            let (start, end) = (clamp(entry.start), clamp(end));
+
+            if start == end {
+                let _ = writeln!(out, "{start:08x}  {:47}  {label}", "");
+                continue;
+            }
+
+            for (n, chunk) in bytes[start..end].chunks(16).enumerate() {
+                let hex: Vec<_> = chunk.iter().map(|x| format!("{x:02x}")).collect();
+                let label = if n == 0 { label.as_str() } else { "" };
+                let _ = writeln!(out, "{:08x}  {:47}  {label}", start + n * 16, hex.join(" "));
+            }
+        }
+
+        out
+    }
+}
+
+// keeps the trace position up to date
+struct TraceRead<R> {
+    inner: R,
+    pos: Arc<AtomicU64>,
+}
+
+impl<R: Read> Read for TraceRead<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.pos.fetch_add(n as u64, Ordering::Relaxed);
+        Ok(n)
+    }
+}
+
+// like T::mt_deserialize::<C>, also returning the trace up to where decoding stopped
+pub fn mt_deserialize_traced<C: MtCfg, T: MtDeserialize>(
+    reader: &mut impl Read,
+) -> (Result<T, DeserializeError>, Trace) {
+    mt_deserialize_traced_with::<C, T>(reader, &mut DeserializeCtx::default())
+}
+
+// same with the limits, version and user context of ctx, its trace is replaced
+pub fn mt_deserialize_traced_with<C: MtCfg, T: MtDeserialize>(
+    reader: &mut impl Read,
+    ctx: &mut DeserializeCtx,
+) -> (Result<T, DeserializeError>, Trace) {
+    let trace = Trace::default();
+    let mut reader = TraceRead {
+        inner: reader,
+        pos: trace.pos.clone(),
+    };
+
+    ctx.trace = Some(trace);
+    let res = T::mt_deserialize_with::<C>(&mut reader, ctx);
+
+    (res, ctx.trace.take().unwrap_or_default())
+}
+
+// lets derived code format field values with Debug where the type has it:
+// (&TraceValue(x)).trace_debug() picks TraceDebug if T: Debug and TraceNoDebug otherwise
+#[doc(hidden)]
+pub struct TraceValue<'a, T>(pub &'a T);
+
+#[doc(hidden)]
+pub trait TraceDebug {
+    fn trace_debug(&self) -> Option<String>;
+}
+
+impl<T: Debug> TraceDebug for TraceValue<'_, T> {
+    fn trace_debug(&self) -> Option<String> {
+        Some(format!("{:?}", self.0))
+    }
+}
+
+#[doc(hidden)]
+pub trait TraceNoDebug {
+    fn trace_debug(&self) -> Option<String>;
+}
+
+impl<T> TraceNoDebug for &TraceValue<'_, T> {
+    fn trace_debug(&self) -> Option<String> {
+        None
+    }
+}